@@ -0,0 +1,165 @@
+use super::error::Error;
+use super::model::{DisplayConfigState, MonitorsConfig, PropValue};
+use super::mutter::{self, ApplyMethod};
+use std::time::Duration;
+
+/// Source of display configuration which commands work with.
+pub trait DisplayBackend {
+    fn get_current_state(&self) -> Result<DisplayConfigState, Error>;
+
+    fn apply_monitors_config(
+        &self,
+        serial: u32,
+        method: ApplyMethod,
+        config: &MonitorsConfig,
+    ) -> Result<(), Error>;
+
+    /// Reads property of the display configuration service, e.g. "ApplyMonitorsConfigAllowed".
+    fn get_property(&self, name: &str) -> Result<PropValue, Error>;
+
+    /// Bus name of the display configuration service owner, `None` when it is not running.
+    fn service_owner(&self) -> Result<Option<String>, Error>;
+
+    /// Blocks calling `on_change` whenever connected monitors change.
+    fn watch_monitors_changed(
+        &self,
+        debounce: &Duration,
+        on_change: &mut dyn FnMut(),
+    ) -> Result<(), Error>;
+}
+
+/// Backend communicating with Mutter over session D-Bus.
+pub struct MutterBackend {
+    timeout: Duration,
+}
+
+impl MutterBackend {
+    pub fn new(timeout: Duration) -> MutterBackend {
+        MutterBackend { timeout }
+    }
+}
+
+impl DisplayBackend for MutterBackend {
+    fn get_current_state(&self) -> Result<DisplayConfigState, Error> {
+        mutter::get_current_state(&self.timeout)
+    }
+
+    fn apply_monitors_config(
+        &self,
+        serial: u32,
+        method: ApplyMethod,
+        config: &MonitorsConfig,
+    ) -> Result<(), Error> {
+        mutter::apply_monitors_config(serial, method, config, &self.timeout)
+    }
+
+    fn get_property(&self, name: &str) -> Result<PropValue, Error> {
+        mutter::get_property(name, &self.timeout)
+    }
+
+    fn service_owner(&self) -> Result<Option<String>, Error> {
+        mutter::service_owner(&self.timeout)
+    }
+
+    fn watch_monitors_changed(
+        &self,
+        debounce: &Duration,
+        on_change: &mut dyn FnMut(),
+    ) -> Result<(), Error> {
+        mutter::watch_monitors_changed(debounce, &self.timeout, on_change)
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+    use crate::model::PropValue;
+    use std::cell::RefCell;
+    use std::collections::{HashMap, VecDeque};
+
+    /// In-memory backend with scriptable monitors and errors.
+    #[derive(Default)]
+    pub struct FakeBackend {
+        pub state: RefCell<DisplayConfigState>,
+        pub properties: RefCell<HashMap<String, PropValue>>,
+        /// Configurations passed to [DisplayBackend::apply_monitors_config], including verified ones.
+        pub applied: RefCell<Vec<(ApplyMethod, MonitorsConfig)>>,
+        /// Errors returned by next calls of [DisplayBackend::apply_monitors_config].
+        pub apply_errors: RefCell<VecDeque<Error>>,
+        /// States switched to one after another by [DisplayBackend::watch_monitors_changed].
+        pub hotplug: RefCell<VecDeque<DisplayConfigState>>,
+        pub service_owner: Option<String>,
+    }
+
+    impl FakeBackend {
+        pub fn new(state: DisplayConfigState) -> FakeBackend {
+            FakeBackend {
+                state: RefCell::new(state),
+                service_owner: Some(":1.0".to_string()),
+                ..FakeBackend::default()
+            }
+        }
+    }
+
+    impl DisplayBackend for FakeBackend {
+        fn get_current_state(&self) -> Result<DisplayConfigState, Error> {
+            Ok(self.state.borrow().clone())
+        }
+
+        fn apply_monitors_config(
+            &self,
+            serial: u32,
+            method: ApplyMethod,
+            config: &MonitorsConfig,
+        ) -> Result<(), Error> {
+            self.applied.borrow_mut().push((method, config.clone()));
+
+            if let Some(err) = self.apply_errors.borrow_mut().pop_front() {
+                return Err(err);
+            }
+
+            let mut state = self.state.borrow_mut();
+
+            if serial != state.serial {
+                Err(dbus::Error::new_custom(
+                    mutter::ACCESS_DENIED_ERROR,
+                    "The requested configuration is based on stale information",
+                ))?
+            }
+
+            if method != ApplyMethod::Verify {
+                state.apply(config);
+            }
+
+            Ok(())
+        }
+
+        fn get_property(&self, name: &str) -> Result<PropValue, Error> {
+            match self.properties.borrow().get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(format!("No such property '{}'", name))?,
+            }
+        }
+
+        fn service_owner(&self) -> Result<Option<String>, Error> {
+            Ok(self.service_owner.clone())
+        }
+
+        fn watch_monitors_changed(
+            &self,
+            _debounce: &Duration,
+            on_change: &mut dyn FnMut(),
+        ) -> Result<(), Error> {
+            loop {
+                let next = self.hotplug.borrow_mut().pop_front();
+                match next {
+                    Some(state) => {
+                        *self.state.borrow_mut() = state;
+                        on_change();
+                    }
+                    None => return Ok(()),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Mock of Mutter's org.gnome.Mutter.DisplayConfig D-Bus service backed by a
+//! JSON fixture, so that presets can be saved and applied without GNOME.
+
+use clap::{arg, command, ArgAction};
+use log::{error, LevelFilter};
+use service::{Fixture, MockMutter};
+use std::process::exit;
+
+mod service;
+
+fn main() {
+    let matches = command!()
+        .name("mock-mutter")
+        .about("Serve org.gnome.Mutter.DisplayConfig on the session bus using monitors from a fixture")
+        .arg(arg!(<FIXTURE>).help("JSON file with display configuration state, in the same format as display configuration of a preset"))
+        .arg(
+            arg!(-v --verbose)
+                .help("Log every D-Bus call")
+                .action(ArgAction::SetTrue),
+        )
+        .get_matches();
+
+    simple_logger::SimpleLogger::new()
+        .with_level(if matches.get_flag("verbose") {
+            LevelFilter::Debug
+        } else {
+            LevelFilter::Info
+        })
+        .without_timestamps()
+        .init()
+        .unwrap();
+
+    let fixture_path = matches.get_one::<String>("FIXTURE").unwrap();
+
+    if let Err(e) = run(fixture_path) {
+        error!("{}", e);
+        exit(1);
+    }
+}
+
+fn run(fixture_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let fixture: Fixture = serde_json::from_str(&std::fs::read_to_string(fixture_path)?)?;
+    service::serve(MockMutter::new(fixture))
+}
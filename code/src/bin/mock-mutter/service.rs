@@ -0,0 +1,576 @@
+use dbus::arg::PropMap;
+use dbus::blocking::LocalConnection;
+use dbus::strings::{Interface, Path};
+use dbus::MethodErr;
+use dbus_tree::{Access, Factory};
+use log::{debug, info};
+use mutter_display_presets::model::{
+    DisplayConfigState, LayoutMode, LogicalMonitorConfig, MonitorConfig, MonitorInfo,
+    MonitorsConfig, PropValue,
+};
+use mutter_display_presets::mutter::{
+    self, ApplyMonitorsConfigLogicalMonitor, GetCurrentStateLogicalMonitor, GetCurrentStateMode,
+    GetCurrentStateMonitor, GetCurrentStateMonitorInfo, ACCESS_DENIED_ERROR, DESTINATION,
+    INTERFACE, INVALID_ARGS_ERROR, LIMITS_EXCEEDED_ERROR, PATH,
+};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Monitors served by the mock. The state has the same format as display
+/// configuration of a saved preset.
+#[derive(Debug, Deserialize)]
+pub struct Fixture {
+    #[serde(flatten)]
+    pub state: DisplayConfigState,
+    #[serde(default = "default_apply_monitors_config_allowed")]
+    pub apply_monitors_config_allowed: bool,
+    #[serde(default)]
+    pub panel_orientation_managed: bool,
+    #[serde(default)]
+    pub night_light_supported: bool,
+}
+
+fn default_apply_monitors_config_allowed() -> bool {
+    true
+}
+
+/// Display configuration service checking configurations the way Mutter does.
+pub struct MockMutter {
+    fixture: Fixture,
+}
+
+impl MockMutter {
+    pub fn new(fixture: Fixture) -> MockMutter {
+        MockMutter { fixture }
+    }
+
+    pub fn state(&self) -> &DisplayConfigState {
+        &self.fixture.state
+    }
+
+    /// Handles ApplyMonitorsConfig call. Returns whether the state has changed.
+    pub fn apply_monitors_config(
+        &mut self,
+        serial: u32,
+        method: u32,
+        config: &MonitorsConfig,
+    ) -> Result<bool, MethodErr> {
+        if serial != self.fixture.state.serial {
+            Err((
+                ACCESS_DENIED_ERROR,
+                "The requested configuration is based on stale information",
+            ))?
+        }
+
+        if !self.fixture.apply_monitors_config_allowed {
+            Err((
+                ACCESS_DENIED_ERROR,
+                "Changing monitor configuration is not allowed",
+            ))?
+        }
+
+        if method > 2 {
+            Err((INVALID_ARGS_ERROR, "Invalid method"))?
+        }
+
+        self.verify(config)?;
+
+        if method == 0 {
+            debug!("Configuration verified");
+            return Ok(false);
+        }
+
+        self.fixture.state.apply(config);
+        info!(
+            "Configuration applied {}, new serial is {}",
+            if method == 2 {
+                "persistently"
+            } else {
+                "temporarily"
+            },
+            self.fixture.state.serial
+        );
+
+        Ok(true)
+    }
+
+    fn verify(&self, config: &MonitorsConfig) -> Result<(), MethodErr> {
+        let state = &self.fixture.state;
+        let layout_mode = match config.properties.get("layout-mode") {
+            Some(value) if !state.supports_changing_layout_mode() => {
+                debug!("Layout mode {} requested", value);
+                Err((INVALID_ARGS_ERROR, "Can't set layout mode"))?
+            }
+            Some(value) => match value.as_u32() {
+                Some(1) => LayoutMode::Logical,
+                Some(2) => LayoutMode::Physical,
+                _ => Err((INVALID_ARGS_ERROR, "Invalid layout mode specified"))?,
+            },
+            None => state.layout_mode(),
+        };
+
+        if config.logical_monitors.is_empty() {
+            Err((INVALID_ARGS_ERROR, "Monitors config incomplete"))?
+        }
+
+        match config
+            .logical_monitors
+            .iter()
+            .filter(|lm| lm.primary)
+            .count()
+        {
+            0 => Err((INVALID_ARGS_ERROR, "Config is missing primary logical"))?,
+            1 => {}
+            _ => Err((
+                INVALID_ARGS_ERROR,
+                "Config contains multiple primary logical monitors",
+            ))?,
+        }
+
+        let mut assigned: Vec<&str> = Vec::new();
+        let (mut screen_width, mut screen_height) = (0, 0);
+
+        for lm in &config.logical_monitors {
+            if lm.transform > 7 {
+                Err((
+                    INVALID_ARGS_ERROR,
+                    format!("Invalid transform {} specified", lm.transform),
+                ))?
+            }
+
+            for monitor in &lm.monitors {
+                if assigned.contains(&monitor.connector.as_str()) {
+                    Err((
+                        INVALID_ARGS_ERROR,
+                        format!(
+                            "Monitor '{}' is assigned to multiple logical monitors",
+                            monitor.connector
+                        ),
+                    ))?
+                }
+                assigned.push(&monitor.connector);
+            }
+
+            let (width, height) = self.logical_monitor_size(lm, layout_mode)?;
+            screen_width = screen_width.max(lm.x + width);
+            screen_height = screen_height.max(lm.y + height);
+        }
+
+        let max_screen_size = state
+            .properties
+            .get("max-screen-size")
+            .and_then(PropValue::as_fields)
+            .and_then(|fields| match fields {
+                [width, height] => Some((width.as_i32()?, height.as_i32()?)),
+                _ => None,
+            });
+
+        if let Some((max_width, max_height)) = max_screen_size {
+            if screen_width > max_width || screen_height > max_height {
+                Err((
+                    LIMITS_EXCEEDED_ERROR,
+                    format!(
+                        "Screen size {}x{} exceeds maximum {}x{}",
+                        screen_width, screen_height, max_width, max_height
+                    ),
+                ))?
+            }
+        }
+
+        Ok(())
+    }
+
+    fn logical_monitor_size(
+        &self,
+        lm: &LogicalMonitorConfig,
+        layout_mode: LayoutMode,
+    ) -> Result<(i32, i32), MethodErr> {
+        let mut size = None;
+
+        for monitor_config in &lm.monitors {
+            let monitor = self
+                .fixture
+                .state
+                .monitors
+                .iter()
+                .find(|m| m.monitor_info.connector == monitor_config.connector)
+                .ok_or_else(|| {
+                    MethodErr::from((
+                        INVALID_ARGS_ERROR,
+                        format!("Invalid connector '{}' specified", monitor_config.connector),
+                    ))
+                })?;
+
+            let mode = monitor
+                .modes
+                .iter()
+                .find(|m| m.id == monitor_config.mode_id)
+                .ok_or_else(|| {
+                    MethodErr::from((
+                        INVALID_ARGS_ERROR,
+                        format!("Invalid mode '{}' specified", monitor_config.mode_id),
+                    ))
+                })?;
+
+            if !mode
+                .supported_scales
+                .iter()
+                .any(|s| (s - lm.scale).abs() < 0.0001)
+            {
+                Err((
+                    INVALID_ARGS_ERROR,
+                    format!(
+                        "Scale {} not valid for resolution {}x{}",
+                        lm.scale, mode.width, mode.height
+                    ),
+                ))?
+            }
+
+            let mode_size = (mode.width, mode.height);
+            match size {
+                Some(size) if size != mode_size => Err((
+                    INVALID_ARGS_ERROR,
+                    "Monitor modes in logical monitor conflict",
+                ))?,
+                _ => size = Some(mode_size),
+            }
+        }
+
+        let (mut width, mut height) = size.unwrap_or_default();
+        if lm.transform % 2 == 1 {
+            (width, height) = (height, width);
+        }
+        if layout_mode == LayoutMode::Logical {
+            width = (width as f64 / lm.scale).round() as i32;
+            height = (height as f64 / lm.scale).round() as i32;
+        }
+
+        Ok((width, height))
+    }
+}
+
+fn monitor_info_to_dbus(info: &MonitorInfo) -> GetCurrentStateMonitorInfo {
+    (
+        info.connector.clone(),
+        info.vendor.clone(),
+        info.product.clone(),
+        info.serial.clone(),
+    )
+}
+
+fn monitors_to_dbus(state: &DisplayConfigState) -> Vec<GetCurrentStateMonitor> {
+    state
+        .monitors
+        .iter()
+        .map(|m| {
+            let modes: Vec<GetCurrentStateMode> = m
+                .modes
+                .iter()
+                .map(|md| {
+                    (
+                        md.id.clone(),
+                        md.width,
+                        md.height,
+                        md.refresh_rate,
+                        md.preferred_scale,
+                        md.supported_scales.clone(),
+                        mutter::props_to_prop_map(&md.properties),
+                    )
+                })
+                .collect();
+            (
+                monitor_info_to_dbus(&m.monitor_info),
+                modes,
+                mutter::props_to_prop_map(&m.properties),
+            )
+        })
+        .collect()
+}
+
+fn logical_monitors_to_dbus(state: &DisplayConfigState) -> Vec<GetCurrentStateLogicalMonitor> {
+    state
+        .logical_monitors
+        .iter()
+        .map(|lm| {
+            (
+                lm.x,
+                lm.y,
+                lm.scale,
+                lm.transform,
+                lm.primary,
+                lm.monitors.iter().map(monitor_info_to_dbus).collect(),
+                mutter::props_to_prop_map(&lm.properties),
+            )
+        })
+        .collect()
+}
+
+fn monitors_config_from_dbus(
+    logical_monitors: &[ApplyMonitorsConfigLogicalMonitor],
+    properties: &PropMap,
+) -> MonitorsConfig {
+    MonitorsConfig {
+        logical_monitors: logical_monitors
+            .iter()
+            .map(|lm| LogicalMonitorConfig {
+                x: lm.0,
+                y: lm.1,
+                scale: lm.2,
+                transform: lm.3,
+                primary: lm.4,
+                monitors: lm
+                    .5
+                    .iter()
+                    .map(|m| MonitorConfig {
+                        connector: m.0.to_string(),
+                        mode_id: m.1.to_string(),
+                        properties: mutter::prop_map_to_props(&m.2),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        properties: mutter::prop_map_to_props(properties),
+    }
+}
+
+/// Serves the mock on the session bus until the process is terminated.
+pub fn serve(mock: MockMutter) -> Result<(), Box<dyn std::error::Error>> {
+    let conn = LocalConnection::new_session()?;
+    conn.request_name(DESTINATION, false, true, true)?;
+
+    let path: Path<'static> = PATH.into();
+    let interface: Interface<'static> = INTERFACE.into();
+
+    let mock = Rc::new(RefCell::new(mock));
+    let f = Factory::new_fn::<()>();
+    let monitors_changed = Arc::new(f.signal("MonitorsChanged", ()));
+
+    let get_current_state = {
+        let mock = mock.clone();
+        f.method("GetCurrentState", (), move |m| {
+            let mock = mock.borrow();
+            let state = mock.state();
+            debug!("GetCurrentState, serial {}", state.serial);
+            Ok(vec![m
+                .msg
+                .method_return()
+                .append2(state.serial, monitors_to_dbus(state))
+                .append2(
+                    logical_monitors_to_dbus(state),
+                    mutter::props_to_prop_map(&state.properties),
+                )])
+        })
+        .outarg::<u32, _>("serial")
+        .outarg::<Vec<GetCurrentStateMonitor>, _>("monitors")
+        .outarg::<Vec<GetCurrentStateLogicalMonitor>, _>("logical_monitors")
+        .outarg::<PropMap, _>("properties")
+    };
+
+    let apply_monitors_config = {
+        let mock = mock.clone();
+        let monitors_changed = monitors_changed.clone();
+        let (path, interface) = (path.clone(), interface.clone());
+        f.method("ApplyMonitorsConfig", (), move |m| {
+            let (serial, method, logical_monitors, properties): (
+                u32,
+                u32,
+                Vec<ApplyMonitorsConfigLogicalMonitor>,
+                PropMap,
+            ) = m.msg.read4()?;
+            debug!("ApplyMonitorsConfig, serial {}, method {}", serial, method);
+
+            let config = monitors_config_from_dbus(&logical_monitors, &properties);
+            let changed = mock
+                .borrow_mut()
+                .apply_monitors_config(serial, method, &config)
+                .inspect_err(|e| info!("Configuration rejected: {}", e))?;
+
+            let mut messages = vec![m.msg.method_return()];
+            if changed {
+                messages.push(monitors_changed.msg(&path, &interface));
+            }
+            Ok(messages)
+        })
+        .inarg::<u32, _>("serial")
+        .inarg::<u32, _>("method")
+        .inarg::<Vec<ApplyMonitorsConfigLogicalMonitor>, _>("logical_monitors")
+        .inarg::<PropMap, _>("properties")
+    };
+
+    let bool_property = |name: &'static str, get: fn(&Fixture) -> bool| {
+        let mock = mock.clone();
+        f.property::<bool, _>(name, ())
+            .access(Access::Read)
+            .on_get(move |i, _| {
+                i.append(get(&mock.borrow().fixture));
+                Ok(())
+            })
+    };
+
+    let tree = f.tree(()).add(
+        f.object_path(path, ()).introspectable().add(
+            f.interface(interface, ())
+                .add_m(get_current_state)
+                .add_m(apply_monitors_config)
+                .add_s(monitors_changed)
+                .add_p(bool_property("ApplyMonitorsConfigAllowed", |f| {
+                    f.apply_monitors_config_allowed
+                }))
+                .add_p(bool_property("PanelOrientationManaged", |f| {
+                    f.panel_orientation_managed
+                }))
+                .add_p(bool_property("NightLightSupported", |f| {
+                    f.night_light_supported
+                })),
+        ),
+    );
+    tree.start_receive(&conn);
+
+    println!("Serving {} as {}", DESTINATION, conn.unique_name());
+
+    loop {
+        conn.process(Duration::from_secs(1))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mutter_display_presets::model::{Mode, Monitor, Props};
+
+    fn mock(max_screen_size: Option<(i32, i32)>) -> MockMutter {
+        let mut properties = Props::new();
+        if let Some((width, height)) = max_screen_size {
+            properties.insert(
+                "max-screen-size".to_string(),
+                PropValue::Struct(vec![PropValue::Int32(width), PropValue::Int32(height)]),
+            );
+        }
+
+        let mode = |id: &str, width: i32, height: i32| Mode {
+            id: id.to_string(),
+            width,
+            height,
+            refresh_rate: 60.0,
+            preferred_scale: 1.0,
+            supported_scales: vec![1.0, 2.0],
+            properties: Props::new(),
+        };
+
+        MockMutter::new(Fixture {
+            state: DisplayConfigState {
+                serial: 1,
+                monitors: vec![Monitor {
+                    monitor_info: MonitorInfo {
+                        connector: "DP-1".to_string(),
+                        vendor: "GSM".to_string(),
+                        product: "LG".to_string(),
+                        serial: "1".to_string(),
+                    },
+                    modes: vec![
+                        mode("1920x1080@60", 1920, 1080),
+                        mode("3840x2160@60", 3840, 2160),
+                    ],
+                    properties: Props::new(),
+                }],
+                logical_monitors: vec![],
+                properties,
+            },
+            apply_monitors_config_allowed: true,
+            panel_orientation_managed: false,
+            night_light_supported: false,
+        })
+    }
+
+    fn config(mode_id: &str, scale: f64) -> MonitorsConfig {
+        MonitorsConfig {
+            logical_monitors: vec![LogicalMonitorConfig {
+                x: 0,
+                y: 0,
+                scale,
+                transform: 0,
+                primary: true,
+                monitors: vec![MonitorConfig {
+                    connector: "DP-1".to_string(),
+                    mode_id: mode_id.to_string(),
+                    properties: Props::new(),
+                }],
+            }],
+            properties: Props::new(),
+        }
+    }
+
+    fn error(result: Result<bool, MethodErr>) -> (String, String) {
+        let err = result.unwrap_err();
+        (err.errorname().to_string(), err.description().to_string())
+    }
+
+    #[test]
+    fn verifies_without_changing_state() {
+        let mut mock = mock(None);
+
+        assert!(!mock
+            .apply_monitors_config(1, 0, &config("3840x2160@60", 2.0))
+            .unwrap());
+        assert_eq!(1, mock.state().serial);
+    }
+
+    #[test]
+    fn applies_config_and_bumps_serial() {
+        let mut mock = mock(None);
+
+        assert!(mock
+            .apply_monitors_config(1, 1, &config("3840x2160@60", 2.0))
+            .unwrap());
+        assert_eq!(2, mock.state().serial);
+        assert_eq!(
+            "3840x2160@60",
+            mock.state().monitors[0].get_current_mode_id().unwrap()
+        );
+    }
+
+    #[test]
+    fn denies_stale_serial() {
+        assert_eq!(
+            (
+                ACCESS_DENIED_ERROR.to_string(),
+                "The requested configuration is based on stale information".to_string()
+            ),
+            error(mock(None).apply_monitors_config(0, 1, &config("1920x1080@60", 1.0)))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_args() {
+        assert_eq!(
+            (
+                INVALID_ARGS_ERROR.to_string(),
+                "Invalid mode '1280x720@60' specified".to_string()
+            ),
+            error(mock(None).apply_monitors_config(1, 0, &config("1280x720@60", 1.0)))
+        );
+        assert_eq!(
+            (
+                INVALID_ARGS_ERROR.to_string(),
+                "Scale 1.5 not valid for resolution 1920x1080".to_string()
+            ),
+            error(mock(None).apply_monitors_config(1, 0, &config("1920x1080@60", 1.5)))
+        );
+    }
+
+    #[test]
+    fn rejects_screen_exceeding_limits() {
+        let mut mock = mock(Some((2048, 2048)));
+
+        assert!(mock
+            .apply_monitors_config(1, 0, &config("3840x2160@60", 2.0))
+            .is_ok());
+        assert_eq!(
+            LIMITS_EXCEEDED_ERROR.to_string(),
+            error(mock.apply_monitors_config(1, 0, &config("3840x2160@60", 1.0))).0
+        );
+    }
+}
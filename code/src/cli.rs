@@ -1,8 +1,21 @@
-use super::model::Preset;
-use super::{config_file, defaults, mutter, out};
+use super::backend::DisplayBackend;
+use super::config_format::ConfigFormat;
+use super::diff::{self, PresetChange};
+use super::error::Error;
+use super::export::{self, Format};
+use super::model::{
+    Condition, Configuration, DisplayConfigState, MonitorsConfig, Preset, PresetConfig,
+    SelectionRules,
+};
+use super::mutter::ApplyMethod;
+use super::resolve::ResolveOptions;
+use super::validation::{self, Verdict};
+use super::{
+    config_file, defaults, doctor, history, monitors_xml, out, prompt, resolve, selection,
+};
 use clap::{arg, command, Arg, ArgAction};
-use log::{debug, info};
-use std::error::Error;
+use log::{debug, error, info, warn};
+use std::io::Read;
 use std::time::Duration;
 
 #[derive(Debug)]
@@ -10,89 +23,480 @@ pub struct GenericOptions {
     pub config_path: String,
     pub verbose: bool,
     pub timeout: Duration,
+    /// Number of previous versions of the configuration file kept in history.
+    pub history_depth: usize,
+    /// Command line recorded in history with configuration written by the command.
+    pub command_line: String,
 }
 
 pub trait Command {
-    fn execute(&self, options: &GenericOptions) -> Result<(), Box<dyn std::error::Error>>;
+    fn execute(&self, options: &GenericOptions, backend: &dyn DisplayBackend) -> Result<(), Error>;
 }
 
 struct SaveCommand {
     name: String,
     force: bool,
+    dry_run: bool,
 }
 
 impl Command for SaveCommand {
-    fn execute(&self, options: &GenericOptions) -> Result<(), Box<dyn std::error::Error>> {
+    fn execute(&self, options: &GenericOptions, backend: &dyn DisplayBackend) -> Result<(), Error> {
         info!("Saving current display configuration as '{}'", self.name);
 
         let config_path = &options.config_path;
-        let timeout = &options.timeout;
 
-        let disp_conf_state = mutter::get_current_state(timeout)?;
+        let disp_conf_state = backend.get_current_state()?;
+        let _lock = config_file::lock_config(config_path)?;
         let mut configuration = config_file::read_config(config_path)?;
+        let before = configuration.clone();
 
-        let existing_pos = configuration
-            .presets
-            .iter()
-            .position(|p| p.name == self.name);
+        put_preset(
+            &mut configuration,
+            Preset {
+                name: self.name.clone(),
+                display_config: PresetConfig::from_state(&disp_conf_state),
+                rules: None,
+            },
+            self.force,
+        )?;
 
-        match existing_pos {
-            Some(i) if self.force => {
-                debug!(
-                    "Preset '{}' will be overridden due to --force option",
-                    self.name
-                );
-                let preset = configuration.presets.get_mut(i).unwrap();
-                preset.display_config = disp_conf_state;
-            }
-            Some(_) => Err(format!(
-                "Preset with name '{}' already exists. Use --force option to override.",
-                self.name
-            ))?,
-            None => {
-                configuration.presets.push(Preset {
-                    name: self.name.clone(),
-                    display_config: disp_conf_state,
-                });
+        write_configuration(options, &before, &configuration, self.dry_run)?;
+
+        info!("Preset '{}' saved successfully", self.name);
+        Ok(())
+    }
+}
+
+/// Adds preset to the configuration. A preset with the same name is overridden only
+/// with `force`, keeping its rules unless the new preset has its own.
+fn put_preset(configuration: &mut Configuration, preset: Preset, force: bool) -> Result<(), Error> {
+    match configuration
+        .presets
+        .iter_mut()
+        .find(|p| p.name == preset.name)
+    {
+        Some(existing) if force => {
+            debug!(
+                "Preset '{}' will be overridden due to --force option",
+                preset.name
+            );
+            existing.display_config = preset.display_config;
+            if preset.rules.is_some() {
+                existing.rules = preset.rules;
             }
         }
+        Some(_) => Err(Error::PresetExists(preset.name))?,
+        None => configuration.presets.push(preset),
+    }
+    Ok(())
+}
 
-        config_file::write_config(config_path, &configuration)?;
+/// Prints presets overwritten or removed by the command and writes configuration,
+/// keeping its previous version in history. With `dry_run`, prints all changes,
+/// including added presets, and writes nothing.
+fn write_configuration(
+    options: &GenericOptions,
+    before: &Configuration,
+    after: &Configuration,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let changes: Vec<PresetChange> = diff::diff_configurations(before, after)
+        .into_iter()
+        .filter(|c| dry_run || !matches!(c, PresetChange::Added(_)))
+        .collect();
+    out::print_changes(&changes);
 
-        info!("Preset '{}' saved successfully", self.name);
-        Ok(())
+    if dry_run {
+        return Ok(());
     }
+
+    history::write_config(
+        &options.config_path,
+        after,
+        &options.command_line,
+        options.history_depth,
+    )
 }
 
 struct ApplyCommand {
     name: String,
     persistent: bool,
+    dry_run: bool,
+    strict: bool,
+    confirm_timeout: Option<Duration>,
 }
 
 impl Command for ApplyCommand {
-    fn execute(&self, options: &GenericOptions) -> Result<(), Box<dyn Error>> {
+    fn execute(&self, options: &GenericOptions, backend: &dyn DisplayBackend) -> Result<(), Error> {
         info!("Applying preset '{}'", self.name);
 
-        let timeout = &options.timeout;
         let config_path = &options.config_path;
 
         let configuration = config_file::read_config(config_path)?;
+        let resolve_options = ResolveOptions {
+            strict: self.strict,
+        };
 
         match configuration.get_preset(&self.name) {
+            Some(preset) if self.dry_run => {
+                let verdict =
+                    validation::verify(backend, &preset.display_config, &resolve_options)?;
+                out::print_verdict(&preset.name, &verdict);
+
+                if let Verdict::Rejected { cause, .. } = verdict {
+                    Err(cause)?
+                }
+            }
             Some(preset) => {
-                let current_state = mutter::get_current_state(timeout)?;
-                let serial = current_state.serial;
+                let method = if self.persistent {
+                    ApplyMethod::Persistent
+                } else {
+                    ApplyMethod::Temporary
+                };
+
+                match &self.confirm_timeout {
+                    Some(confirm_timeout) => {
+                        let snapshot = backend.get_current_state()?;
+
+                        apply_preset(backend, &preset.display_config, method, &resolve_options)?;
+
+                        let confirmed = prompt::confirm(
+                            &format!(
+                                "Keep this display configuration? It will be reverted in {} seconds.",
+                                confirm_timeout.as_secs()
+                            ),
+                            confirm_timeout,
+                        );
+
+                        if !confirmed {
+                            info!("Reverting display configuration");
+                            apply_preset(
+                                backend,
+                                &PresetConfig::from_state(&snapshot),
+                                method,
+                                &ResolveOptions::default(),
+                            )?;
+                            Err("Display configuration was not confirmed and has been reverted.")?
+                        }
+                    }
+                    None => {
+                        apply_preset(backend, &preset.display_config, method, &resolve_options)?
+                    }
+                }
+
+                info!("Preset '{}' applied.", self.name)
+            }
+            None => Err(Error::PresetNotFound(self.name.clone()))?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolves display configuration against currently connected monitors and applies it.
+/// When display configuration changes in between and Mutter denies the stale serial,
+/// configuration is resolved against the new state and applied again.
+fn apply_preset(
+    backend: &dyn DisplayBackend,
+    preset: &PresetConfig,
+    method: ApplyMethod,
+    resolve_options: &ResolveOptions,
+) -> Result<(), Error> {
+    check_apply_allowed(backend)?;
+
+    let mut attempt = 1;
+    let mut backoff = defaults::default_retry_backoff();
+
+    loop {
+        let current_state = backend.get_current_state()?;
+        let config = resolve::resolve_preset(preset, &current_state, resolve_options)?;
+
+        if attempt == 1 && is_panel_orientation_managed(backend) {
+            for connector in managed_orientation_conflicts(&config, &current_state) {
+                warn!(
+                    "Orientation of built-in display {} is managed by Mutter automatically. \
+                    Rotation from the preset may be overridden.",
+                    connector
+                );
+            }
+        }
+
+        match backend.apply_monitors_config(current_state.serial, method, &config) {
+            Err(Error::AccessDenied(message)) if attempt < defaults::default_apply_attempts() => {
+                warn!(
+                    "Mutter denied configuration based on serial {} ({}). Retrying in {} ms",
+                    current_state.serial,
+                    message,
+                    backoff.as_millis()
+                );
+                std::thread::sleep(backoff);
+                attempt += 1;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Fails when Mutter reports that it does not accept configuration changes. Older Mutter
+/// versions without ApplyMonitorsConfigAllowed property are assumed to accept them.
+fn check_apply_allowed(backend: &dyn DisplayBackend) -> Result<(), Error> {
+    match backend.get_property("ApplyMonitorsConfigAllowed") {
+        Ok(allowed) if allowed.as_bool() == Some(false) => Err(Error::ApplyNotAllowed),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            debug!("Can't read ApplyMonitorsConfigAllowed property: {}", e);
+            Ok(())
+        }
+    }
+}
+
+fn is_panel_orientation_managed(backend: &dyn DisplayBackend) -> bool {
+    match backend.get_property("PanelOrientationManaged") {
+        Ok(managed) => managed.as_bool() == Some(true),
+        Err(e) => {
+            debug!("Can't read PanelOrientationManaged property: {}", e);
+            false
+        }
+    }
+}
+
+/// Connectors of built-in displays for which configuration sets different transform
+/// than the current one.
+fn managed_orientation_conflicts<'a>(
+    config: &'a MonitorsConfig,
+    current: &DisplayConfigState,
+) -> Vec<&'a str> {
+    let current_transform = |connector: &str| {
+        current
+            .logical_monitors
+            .iter()
+            .find(|lm| lm.monitors.iter().any(|m| m.connector == connector))
+            .map(|lm| lm.transform)
+            .unwrap_or_default()
+    };
+
+    config
+        .logical_monitors
+        .iter()
+        .flat_map(|lm| lm.monitors.iter().map(move |m| (lm.transform, m)))
+        .filter(|(transform, m)| {
+            current
+                .monitors
+                .iter()
+                .any(|cm| cm.monitor_info.connector == m.connector && cm.is_builtin())
+                && *transform != current_transform(&m.connector)
+        })
+        .map(|(_, m)| m.connector.as_str())
+        .collect()
+}
+
+struct ValidateCommand {
+    name: Option<String>,
+    strict: bool,
+}
 
-                mutter::apply_monitors_config(
-                    serial,
-                    self.persistent,
+impl Command for ValidateCommand {
+    fn execute(&self, options: &GenericOptions, backend: &dyn DisplayBackend) -> Result<(), Error> {
+        info!("Validating presets");
+
+        let configuration = config_file::read_config(&options.config_path)?;
+        let resolve_options = ResolveOptions {
+            strict: self.strict,
+        };
+
+        let presets: Vec<&Preset> = match &self.name {
+            Some(name) => match configuration.get_preset(name) {
+                Some(preset) => vec![preset],
+                None => Err(Error::PresetNotFound(name.clone()))?,
+            },
+            None => configuration.presets.iter().collect(),
+        };
+
+        let mut first_cause = None;
+
+        for preset in presets {
+            let verdict = validation::verify(backend, &preset.display_config, &resolve_options)?;
+            out::print_verdict(&preset.name, &verdict);
+
+            if let Verdict::Rejected { cause, .. } = verdict {
+                first_cause.get_or_insert(cause);
+            }
+        }
+
+        // Exit code tells the cause of the first rejected preset.
+        match first_cause {
+            Some(cause) => Err(cause.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+struct WatchCommand {
+    debounce: Duration,
+}
+
+impl Command for WatchCommand {
+    fn execute(&self, options: &GenericOptions, backend: &dyn DisplayBackend) -> Result<(), Error> {
+        info!("Watching for changes of connected monitors");
+
+        let mut connected_monitors: Option<Vec<String>> = None;
+
+        let mut on_change = || {
+            if let Err(e) = self.on_monitors_changed(options, backend, &mut connected_monitors) {
+                error!("{}", e);
+            }
+        };
+
+        on_change();
+
+        backend.watch_monitors_changed(&self.debounce, &mut on_change)
+    }
+}
+
+impl WatchCommand {
+    fn on_monitors_changed(
+        &self,
+        options: &GenericOptions,
+        backend: &dyn DisplayBackend,
+        connected_monitors: &mut Option<Vec<String>>,
+    ) -> Result<(), Error> {
+        let current_state = backend.get_current_state()?;
+        let monitors = selection::monitor_set(&current_state);
+
+        if connected_monitors.as_ref() == Some(&monitors) {
+            debug!("Connected monitors did not change");
+            return Ok(());
+        }
+
+        *connected_monitors = Some(monitors);
+
+        let configuration = config_file::read_config(&options.config_path)?;
+
+        match selection::find_matching_preset(&configuration, &current_state) {
+            Some(preset) => {
+                info!("Applying preset '{}'", preset.name);
+                apply_preset(
+                    backend,
                     &preset.display_config,
-                    timeout,
+                    ApplyMethod::Temporary,
+                    &ResolveOptions::default(),
                 )?;
+                info!("Preset '{}' applied.", preset.name)
+            }
+            None => info!("No preset matches connected monitors"),
+        }
 
-                info!("Preset '{}' applied.", self.name)
+        Ok(())
+    }
+}
+
+struct AutoCommand {
+    persistent: bool,
+    explain: bool,
+}
+
+impl Command for AutoCommand {
+    fn execute(&self, options: &GenericOptions, backend: &dyn DisplayBackend) -> Result<(), Error> {
+        info!("Selecting preset by rules");
+
+        let configuration = config_file::read_config(&options.config_path)?;
+        let current_state = backend.get_current_state()?;
+
+        let evaluations = selection::evaluate(&configuration, &current_state);
+        let winner = selection::select(&evaluations);
+
+        if self.explain {
+            out::print_evaluations(&evaluations, winner);
+        }
+
+        match winner {
+            Some(evaluation) => {
+                let method = if self.persistent {
+                    ApplyMethod::Persistent
+                } else {
+                    ApplyMethod::Temporary
+                };
+
+                info!("Applying preset '{}'", evaluation.preset.name);
+                apply_preset(
+                    backend,
+                    &evaluation.preset.display_config,
+                    method,
+                    &ResolveOptions::default(),
+                )?;
+                info!("Preset '{}' applied.", evaluation.preset.name)
             }
-            None => Err(format!("Preset '{}' was not found.", &self.name))?,
+            None => Err("No preset satisfies its rules for connected monitors.")?,
+        }
+
+        Ok(())
+    }
+}
+
+struct RulesCommand {
+    name: String,
+    rules: Option<SelectionRules>,
+    clear: bool,
+}
+
+impl Command for RulesCommand {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
+        let _lock = config_file::lock_config(&options.config_path)?;
+        let mut configuration = config_file::read_config(&options.config_path)?;
+        let before = configuration.clone();
+
+        let preset = match configuration
+            .presets
+            .iter_mut()
+            .find(|p| p.name == self.name)
+        {
+            Some(preset) => preset,
+            None => Err(Error::PresetNotFound(self.name.clone()))?,
+        };
+
+        if !self.clear && self.rules.is_none() {
+            out::print_rules(&preset.name, &preset.rules);
+            return Ok(());
+        }
+
+        info!("Updating selection rules of preset '{}'", self.name);
+        preset.rules = self.rules.clone();
+
+        write_configuration(options, &before, &configuration, false)?;
+
+        info!("Selection rules of preset '{}' updated", self.name);
+        Ok(())
+    }
+}
+
+struct DoctorCommand {
+    json: bool,
+}
+
+impl Command for DoctorCommand {
+    fn execute(&self, options: &GenericOptions, backend: &dyn DisplayBackend) -> Result<(), Error> {
+        info!("Checking environment");
+
+        let checks = doctor::run_checks(backend, &options.config_path);
+
+        // The error goes to standard error, so JSON output stays valid.
+        if self.json {
+            out::print_checks_json(&checks);
+        } else {
+            out::print_checks(&checks);
+        }
+
+        let failed = checks
+            .iter()
+            .filter(|c| c.status == doctor::Status::Fail)
+            .count();
+        if failed > 0 {
+            Err(format!("{} check(s) failed.", failed))?
         }
 
         Ok(())
@@ -102,7 +506,11 @@ impl Command for ApplyCommand {
 struct ListCommand {}
 
 impl Command for ListCommand {
-    fn execute(&self, options: &GenericOptions) -> Result<(), Box<dyn Error>> {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
         info!("List available presets");
 
         let configuration = config_file::read_config(&options.config_path)?;
@@ -120,10 +528,16 @@ struct DeleteCommand {
 }
 
 impl Command for DeleteCommand {
-    fn execute(&self, options: &GenericOptions) -> Result<(), Box<dyn Error>> {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
         info!("Deleting preset {}", self.name);
 
+        let _lock = config_file::lock_config(&options.config_path)?;
         let mut configuration = config_file::read_config(&options.config_path)?;
+        let before = configuration.clone();
 
         let preset_pos = configuration
             .presets
@@ -134,10 +548,10 @@ impl Command for DeleteCommand {
             Some(pos) => {
                 configuration.presets.remove(pos);
             }
-            None => Err(format!("Preset '{}' was not found", self.name))?,
+            None => Err(Error::PresetNotFound(self.name.clone()))?,
         }
 
-        config_file::write_config(&options.config_path, &configuration)?;
+        write_configuration(options, &before, &configuration, false)?;
 
         info!("Preset '{}' has been deleted", self.name);
         Ok(())
@@ -151,14 +565,20 @@ struct RenameCommand {
 }
 
 impl Command for RenameCommand {
-    fn execute(&self, options: &GenericOptions) -> Result<(), Box<dyn Error>> {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
         info!("Renaming preset '{}' to '{}'", self.name, self.new_name);
 
         if self.name == self.new_name {
             Err("Source and target names are same. Nothing to do.")?
         }
 
+        let _lock = config_file::lock_config(&options.config_path)?;
         let mut configuration = config_file::read_config(&options.config_path)?;
+        let before = configuration.clone();
 
         let preset_pos = configuration
             .presets
@@ -178,20 +598,17 @@ impl Command for RenameCommand {
                         preset.name = self.new_name.clone();
                         configuration.presets.remove(target_pos);
                     }
-                    Some(_) => Err(format!(
-                        "Preset with name '{}' already exist. To override use --force option",
-                        self.new_name
-                    ))?,
+                    Some(_) => Err(Error::PresetExists(self.new_name.clone()))?,
                     None => {
                         let preset = configuration.presets.get_mut(pos).unwrap();
                         preset.name = self.new_name.clone();
                     }
                 }
             }
-            None => Err(format!("Preset '{}' was not found", self.name))?,
+            None => Err(Error::PresetNotFound(self.name.clone()))?,
         }
 
-        config_file::write_config(&options.config_path, &configuration)?;
+        write_configuration(options, &before, &configuration, false)?;
 
         info!(
             "Preset '{}' has been renamed to '{}'",
@@ -201,95 +618,579 @@ impl Command for RenameCommand {
     }
 }
 
-struct ShowCommand {
-    name: String,
+struct ImportMonitorsXmlCommand {
+    path: Option<String>,
+    name: Option<String>,
+    force: bool,
+    dry_run: bool,
 }
 
-impl Command for ShowCommand {
-    fn execute(&self, options: &GenericOptions) -> Result<(), Box<dyn Error>> {
-        info!("Printing information about preset '{}'", self.name);
+impl Command for ImportMonitorsXmlCommand {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => defaults::default_monitors_xml_path()
+                .ok_or("Can't locate monitors.xml, specify PATH explicitly.")?,
+        };
+        info!("Importing configurations from {}", path);
+
+        let monitors_xml_error = |message: String| Error::MonitorsXml {
+            path: path.clone(),
+            message,
+        };
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| monitors_xml_error(e.to_string()))?;
+        let configs = monitors_xml::parse(&content).map_err(monitors_xml_error)?;
+
+        let _lock = config_file::lock_config(&options.config_path)?;
+        let mut configuration = config_file::read_config(&options.config_path)?;
+        let before = configuration.clone();
+
+        let mut imported = 0;
+        for (i, config) in configs.into_iter().enumerate() {
+            if let Some(duplicate) = configuration
+                .presets
+                .iter()
+                .find(|p| p.display_config.same_layout(&config))
+            {
+                println!(
+                    "Configuration {} is the same as preset '{}', skipped",
+                    i + 1,
+                    duplicate.name
+                );
+                continue;
+            }
+
+            let name = match &self.name {
+                Some(name) => {
+                    // Only the first imported preset takes the name as is.
+                    if imported == 0 {
+                        match configuration.presets.iter().position(|p| &p.name == name) {
+                            Some(pos) if self.force => {
+                                configuration.presets.remove(pos);
+                            }
+                            Some(_) => Err(Error::PresetExists(name.clone()))?,
+                            None => {}
+                        }
+                    }
+                    unique_name(&configuration, name)
+                }
+                None => unique_name(&configuration, &generated_name(&config)),
+            };
+            imported += 1;
+
+            if !self.dry_run {
+                println!("Configuration {} imported as preset '{}'", i + 1, name);
+            }
+            configuration.presets.push(Preset {
+                name,
+                display_config: config,
+                rules: None,
+            });
+        }
+
+        write_configuration(options, &before, &configuration, self.dry_run)?;
+
+        info!("Configurations imported from {}", path);
+        Ok(())
+    }
+}
+
+/// Name of a preset made of connectors of its monitors, e.g. "eDP-1+DP-1".
+fn generated_name(config: &PresetConfig) -> String {
+    config
+        .monitors()
+        .map(|m| m.monitor_info.connector.as_str())
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// The name itself when no preset has it, otherwise the name followed by a number.
+fn unique_name(configuration: &Configuration, name: &str) -> String {
+    let mut candidate = name.to_string();
+    let mut n = 2;
+    while configuration.get_preset(&candidate).is_some() {
+        candidate = format!("{} ({})", name, n);
+        n += 1;
+    }
+    candidate
+}
+
+struct ExportMonitorsXmlCommand {
+    names: Vec<String>,
+    output: Option<String>,
+}
+
+impl Command for ExportMonitorsXmlCommand {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
+        let path = match &self.output {
+            Some(path) => path.clone(),
+            None => defaults::default_monitors_xml_path()
+                .ok_or("Can't locate monitors.xml, specify output file explicitly.")?,
+        };
+        info!("Exporting presets to {}", path);
+
+        let monitors_xml_error = |message: String| Error::MonitorsXml {
+            path: path.clone(),
+            message,
+        };
 
         let configuration = config_file::read_config(&options.config_path)?;
+        let presets: Vec<&Preset> = if self.names.is_empty() {
+            configuration.presets.iter().collect()
+        } else {
+            self.names
+                .iter()
+                .map(|name| {
+                    configuration
+                        .get_preset(name)
+                        .ok_or_else(|| Error::PresetNotFound(name.clone()))
+                })
+                .collect::<Result<_, _>>()?
+        };
 
-        match configuration.get_preset(&self.name) {
-            Some(preset) => {
-                out::print_preset(preset);
+        let elements = presets
+            .iter()
+            .map(|p| {
+                monitors_xml::to_configuration(&p.display_config)
+                    .map_err(|e| monitors_xml_error(format!("Preset '{}': {}", p.name, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let existing = match std::fs::read_to_string(&path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => Err(monitors_xml_error(e.to_string()))?,
+        };
+        let (content, replaced) =
+            monitors_xml::merge(existing.as_deref(), elements).map_err(monitors_xml_error)?;
+
+        config_file::replace_file(std::path::Path::new(&path), content.as_bytes())
+            .map_err(|e| monitors_xml_error(e.to_string()))?;
+
+        for (preset, replaced) in presets.iter().zip(replaced) {
+            if replaced {
+                println!(
+                    "Preset '{}' exported, replacing configuration for the same monitors",
+                    preset.name
+                );
+            } else {
+                println!("Preset '{}' exported", preset.name);
             }
-            None => Err(format!("Preset '{}' was not found", self.name))?,
         }
+        println!("Mutter reads {} when the session starts.", path);
 
         Ok(())
     }
 }
 
-pub struct Cli {
-    pub command: Box<dyn Command>,
-    pub options: GenericOptions,
+struct ExportCommand {
+    names: Vec<String>,
+    format: Format,
+    output: Option<String>,
 }
 
-impl Cli {
-    pub fn parse() -> Result<Cli, String> {
-        let matches = command!()
-            .subcommand_required(true)
-            .propagate_version(true)
-            .arg_required_else_help(true)
-            .subcommands([
-                clap::Command::new("save")
-                    .about("Save current display configuration as a preset")
-                    .arg(
-                        arg!([NAME])
-                            .required(true)
-                            .help("Preset name")
-                    )
-                    .arg(
-                        Arg::new("force")
-                            .short('f')
-                            .long("force")
-                            .help("Override existing preset with the same name if exist")
-                            .action(ArgAction::SetTrue)
-                            .required(false)
-                    ),
-                clap::Command::new("apply")
-                    .about("Apply display configuration from specified preset")
-                    .arg(
-                        arg!([NAME])
-                            .required(true)
-                            .help("Preset name")
-                    ).arg(
-                    Arg::new("persistent")
-                        .short('p')
-                        .long("persistent")
-                        .help("Persistent mode. Applied configuration will remain active after Mutter restart. Requires manual confirmation from user.")
-                        .action(ArgAction::SetTrue)
-                        .required(false)
-                ),
-                clap::Command::new("list").about("List available presets"),
-                clap::Command::new("delete")
-                    .about("Delete preset with specified name")
-                    .arg(
-                        arg!([NAME])
-                            .required(true)
-                            .help("Preset name")
-                    ),
-                clap::Command::new("rename")
-                    .about("Rename display configuration preset")
-                    .arg(
-                        arg!([NAME])
-                            .required(true)
-                            .help("Preset name")
-                    )
-                    .arg(
-                        arg!([NEW_NAME])
-                            .required(true)
-                            .help("Preset new name")
-                    )
-                    .arg(
-                        Arg::new("force")
-                            .short('f')
-                            .long("force")
-                            .help("Override existing preset with the same name if exist")
-                            .action(ArgAction::SetTrue)
-                            .required(false)
+impl Command for ExportCommand {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
+        info!("Exporting presets as {:?}", self.format);
+
+        let configuration = config_file::read_config(&options.config_path)?;
+        let presets: Vec<&Preset> = if self.names.is_empty() {
+            configuration.presets.iter().collect()
+        } else {
+            self.names
+                .iter()
+                .map(|name| {
+                    configuration
+                        .get_preset(name)
+                        .ok_or_else(|| Error::PresetNotFound(name.clone()))
+                })
+                .collect::<Result<_, _>>()?
+        };
+
+        if self.format.is_single_preset() && presets.len() != 1 {
+            Err("Only a single preset can be exported in this format. Specify name of the preset to export.")?
+        }
+
+        let content = export::export(&presets, self.format, &export::Manufacturers::load());
+
+        match self.output.as_deref() {
+            Some("-") | None => print!("{}", content),
+            Some(path) => write_export(path, &content, self.format.is_script())
+                .map_err(|e| format!("Can't write '{}': {}", path, e))?,
+        }
+
+        Ok(())
+    }
+}
+
+struct ImportCommand {
+    path: String,
+    name: Option<String>,
+    force: bool,
+    dry_run: bool,
+}
+
+impl Command for ImportCommand {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
+        let (source, content) = if self.path == "-" {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .map_err(|e| format!("Can't read standard input: {}", e))?;
+            ("standard input".to_string(), content)
+        } else {
+            let content = std::fs::read_to_string(&self.path)
+                .map_err(|e| format!("Can't read '{}': {}", self.path, e))?;
+            (self.path.clone(), content)
+        };
+
+        let mut preset = config_file::parse_preset_document(&content, &source)?;
+        if let Some(name) = &self.name {
+            preset.name = name.clone();
+        }
+        let name = preset.name.clone();
+        info!("Importing preset '{}' from {}", name, source);
+
+        let _lock = config_file::lock_config(&options.config_path)?;
+        let mut configuration = config_file::read_config(&options.config_path)?;
+        let before = configuration.clone();
+
+        put_preset(&mut configuration, preset, self.force)?;
+        write_configuration(options, &before, &configuration, self.dry_run)?;
+
+        if !self.dry_run {
+            println!("Preset '{}' imported", name);
+        }
+        Ok(())
+    }
+}
+
+struct ConvertCommand {
+    path: String,
+    force: bool,
+}
+
+impl Command for ConvertCommand {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
+        info!(
+            "Converting configuration from {} to {}",
+            options.config_path, self.path
+        );
+
+        if !self.force && std::path::Path::new(&self.path).exists() {
+            Err(format!(
+                "File '{}' already exists. Use --force to overwrite it",
+                self.path
+            ))?
+        }
+
+        let count = config_file::convert_config(&options.config_path, &self.path)?;
+
+        println!(
+            "{} preset(s) converted from '{}' to '{}'",
+            count, options.config_path, self.path
+        );
+        if ConfigFormat::of(&self.path) == ConfigFormat::Yaml {
+            println!(
+                "Note: only the comment at the top of a YAML file is kept when the application rewrites it, \
+                 other comments are lost"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Writes exported content. New scripts are made executable, permissions of existing
+/// files are kept.
+fn write_export(path: &str, content: &str, script: bool) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::path::Path::new(path);
+    let existed = path.exists();
+    config_file::replace_file(path, content.as_bytes())?;
+
+    if script && !existed {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+struct HistoryCommand {}
+
+impl Command for HistoryCommand {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
+        info!("Listing versions of configuration file");
+
+        let entries = history::read_history(&options.config_path)?;
+        if entries.is_empty() {
+            println!("No versions recorded yet.");
+        }
+        out::print_history(&entries);
+
+        Ok(())
+    }
+}
+
+/// Restores a version of the configuration file from history. Without version, the
+/// newest version which differs from the current configuration is restored.
+struct RestoreCommand {
+    version: Option<usize>,
+    dry_run: bool,
+}
+
+impl Command for RestoreCommand {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
+        let _lock = config_file::lock_config(&options.config_path)?;
+
+        let current = match config_file::read_config(&options.config_path) {
+            Ok(current) => current,
+            Err(e) => {
+                warn!("Restoring over unreadable configuration: {}", e);
+                Configuration { presets: vec![] }
+            }
+        };
+        let entries = history::read_history(&options.config_path)?;
+
+        let (version, entry) = match self.version {
+            Some(version) => match entries.get(version) {
+                Some(entry) => (version, entry),
+                None => Err(format!(
+                    "Version {} is not in history. Use 'history' command to see available versions.",
+                    version
+                ))?,
+            },
+            None => match entries
+                .iter()
+                .enumerate()
+                .find(|(_, e)| e.configuration != current)
+            {
+                Some(found) => found,
+                None => Err("There is no previous version to undo to.")?,
+            },
+        };
+
+        info!("Restoring version {} ({})", version, entry.command);
+        out::print_changes(&diff::diff_configurations(&current, &entry.configuration));
+
+        if self.dry_run {
+            return Ok(());
+        }
+
+        history::write_config(
+            &options.config_path,
+            &entry.configuration,
+            &options.command_line,
+            options.history_depth,
+        )?;
+
+        info!("Version {} restored", version);
+        Ok(())
+    }
+}
+
+struct ShowCommand {
+    name: String,
+}
+
+impl Command for ShowCommand {
+    fn execute(
+        &self,
+        options: &GenericOptions,
+        _backend: &dyn DisplayBackend,
+    ) -> Result<(), Error> {
+        info!("Printing information about preset '{}'", self.name);
+
+        let configuration = config_file::read_config(&options.config_path)?;
+
+        match configuration.get_preset(&self.name) {
+            Some(preset) => {
+                out::print_preset(preset);
+            }
+            None => Err(Error::PresetNotFound(self.name.clone()))?,
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Cli {
+    pub command: Box<dyn Command>,
+    pub options: GenericOptions,
+}
+
+impl Cli {
+    pub fn parse() -> Result<Cli, String> {
+        let matches = command!()
+            .subcommand_required(true)
+            .propagate_version(true)
+            .arg_required_else_help(true)
+            .subcommands([
+                clap::Command::new("save")
+                    .about("Save current display configuration as a preset")
+                    .arg(
+                        arg!([NAME])
+                            .required(true)
+                            .help("Preset name")
+                    )
+                    .arg(
+                        Arg::new("force")
+                            .short('f')
+                            .long("force")
+                            .help("Override existing preset with the same name if exist")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    )
+                    .arg(
+                        Arg::new("dry-run")
+                            .long("dry-run")
+                            .help("Only print presets which would be changed")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    ),
+                clap::Command::new("apply")
+                    .about("Apply display configuration from specified preset")
+                    .arg(
+                        arg!([NAME])
+                            .required(true)
+                            .help("Preset name")
+                    ).arg(
+                    Arg::new("persistent")
+                        .short('p')
+                        .long("persistent")
+                        .help("Persistent mode. Applied configuration will remain active after Mutter restart. Requires manual confirmation from user.")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("dry-run")
+                        .required(false)
+                ).arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("Only check whether Mutter would accept the preset, without applying it")
+                        .action(ArgAction::SetTrue)
+                        .required(false)
+                ).arg(
+                    Arg::new("strict")
+                        .long("strict")
+                        .help("Fail when a saved mode is not supported by the monitor anymore, instead of using the closest available mode")
+                        .action(ArgAction::SetTrue)
+                        .required(false)
+                ).arg(
+                    Arg::new("confirm-timeout")
+                        .long("confirm-timeout")
+                        .value_name("SECONDS")
+                        .help("Ask for confirmation after applying the preset and revert to the previous configuration if it is not confirmed within specified number of seconds")
+                        .value_parser(clap::value_parser!(u64))
+                        .conflicts_with("dry-run")
+                        .action(ArgAction::Set)
+                        .required(false)
+                ),
+                clap::Command::new("validate")
+                    .about("Check whether Mutter would accept presets, without applying them")
+                    .arg(
+                        arg!([NAME])
+                            .required(false)
+                            .help("Preset name. All presets are validated when not specified")
+                    )
+                    .arg(
+                        Arg::new("strict")
+                            .long("strict")
+                            .help("Treat saved modes which are not supported by monitors anymore as errors")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    ),
+                clap::Command::new("watch")
+                    .about("Keep running and apply preset matching connected monitors whenever they change")
+                    .arg(
+                        Arg::new("debounce")
+                            .long("debounce")
+                            .value_name("MILLISECONDS")
+                            .help("Wait until monitors stop changing for specified time before applying a preset. 1000 milliseconds by default")
+                            .value_parser(clap::value_parser!(u64))
+                            .action(ArgAction::Set)
+                            .required(false)
+                    ),
+                clap::Command::new("auto")
+                    .about("Apply preset selected by rules for connected monitors")
+                    .arg(
+                        Arg::new("persistent")
+                            .short('p')
+                            .long("persistent")
+                            .help("Persistent mode. Applied configuration will remain active after Mutter restart. Requires manual confirmation from user.")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    )
+                    .arg(
+                        Arg::new("explain")
+                            .long("explain")
+                            .help("Print how every preset scored against its rules")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    ),
+                rules_command(),
+                clap::Command::new("doctor")
+                    .about("Check environment and configuration file for common problems")
+                    .arg(
+                        Arg::new("json")
+                            .long("json")
+                            .help("Print results as JSON, e.g. to attach them to a bug report")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    ),
+                clap::Command::new("list").about("List available presets"),
+                clap::Command::new("delete")
+                    .about("Delete preset with specified name")
+                    .arg(
+                        arg!([NAME])
+                            .required(true)
+                            .help("Preset name")
+                    ),
+                clap::Command::new("rename")
+                    .about("Rename display configuration preset")
+                    .arg(
+                        arg!([NAME])
+                            .required(true)
+                            .help("Preset name")
+                    )
+                    .arg(
+                        arg!([NEW_NAME])
+                            .required(true)
+                            .help("Preset new name")
+                    )
+                    .arg(
+                        Arg::new("force")
+                            .short('f')
+                            .long("force")
+                            .help("Override existing preset with the same name if exist")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
                     ),
                     clap::Command::new("show")
                         .about("Print information about preset")
@@ -298,6 +1199,153 @@ impl Cli {
                                 .required(true)
                                 .help("Preset name")
                         ),
+                clap::Command::new("import-monitors-xml")
+                    .about("Import configurations stored by Mutter in monitors.xml as presets")
+                    .long_about("Import configurations stored by Mutter in monitors.xml as presets. Configurations \
+                        which are the same as existing presets are skipped. Names of presets are made of connectors \
+                        unless --name is given.")
+                    .arg(
+                        arg!([PATH])
+                            .required(false)
+                            .help("Path to monitors.xml. '$XDG_CONFIG_HOME/monitors.xml' or '$HOME/.config/monitors.xml' by default")
+                    )
+                    .arg(
+                        Arg::new("name")
+                            .long("name")
+                            .help("Name of the imported preset. Following presets get a number appended, e.g. 'Work (2)'")
+                            .action(ArgAction::Set)
+                    )
+                    .arg(
+                        Arg::new("force")
+                            .short('f')
+                            .long("force")
+                            .help("Override existing preset with the same name if exist")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    )
+                    .arg(
+                        Arg::new("dry-run")
+                            .long("dry-run")
+                            .help("Only print presets which would be changed")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    ),
+                clap::Command::new("export-monitors-xml")
+                    .about("Export presets to monitors.xml, where Mutter keeps persistent configurations")
+                    .long_about("Export presets to monitors.xml, where Mutter keeps persistent configurations. \
+                        Configurations for other sets of monitors in the file are kept. The same layouts can be used \
+                        on the login screen by exporting to GDM's monitors.xml, e.g. '/var/lib/gdm3/.config/monitors.xml'.")
+                    .arg(
+                        arg!([NAME]...)
+                            .required(false)
+                            .help("Names of presets. All presets are exported when not specified")
+                    )
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_name("PATH")
+                            .help("File to export to. '$XDG_CONFIG_HOME/monitors.xml' or '$HOME/.config/monitors.xml' by default")
+                            .action(ArgAction::Set)
+                    ),
+                clap::Command::new("export")
+                    .about("Export a preset to share it, or presets as configuration of other display tools")
+                    .arg(
+                        arg!([NAME]...)
+                            .required(false)
+                            .help("Names of presets. All presets are exported when not specified")
+                    )
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .help("Format of the export: preset document for 'import' command, kanshi profiles, sway output directives, \
+                                or xrandr or gnome-monitor-config shell script. kanshi and sway outputs are identified by \
+                                'make model serial' when hwdata knows the manufacturer of the monitor's PNP id, by connector otherwise")
+                            .value_parser(["preset", "kanshi", "sway", "xrandr", "gnome-monitor-config"])
+                            .default_value("preset")
+                            .action(ArgAction::Set)
+                    )
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_name("PATH")
+                            .help("File to write to, '-' for standard output. Standard output by default")
+                            .action(ArgAction::Set)
+                    ),
+                clap::Command::new("import")
+                    .about("Import preset exported with 'export' command")
+                    .arg(
+                        arg!(<PATH>)
+                            .help("File with the preset, '-' for standard input")
+                    )
+                    .arg(
+                        Arg::new("as")
+                            .long("as")
+                            .value_name("NAME")
+                            .help("Name of the imported preset. Name stored in the file by default")
+                            .action(ArgAction::Set)
+                    )
+                    .arg(
+                        Arg::new("force")
+                            .short('f')
+                            .long("force")
+                            .help("Override existing preset with the same name if exist")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    )
+                    .arg(
+                        Arg::new("dry-run")
+                            .long("dry-run")
+                            .help("Only print presets which would be changed")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    ),
+                clap::Command::new("convert")
+                    .about("Write the configuration file in another format")
+                    .long_about("Write the configuration file in another format: TOML for '.toml' files, YAML for '.yaml' \
+                        and '.yml' files, JSON otherwise. Comments can't be carried over between formats. Only the comment at the top of \
+                        a YAML file survives when the application rewrites it.")
+                    .arg(
+                        arg!(<PATH>)
+                            .help("File to write the configuration to")
+                    )
+                    .arg(
+                        Arg::new("force")
+                            .short('f')
+                            .long("force")
+                            .help("Overwrite the file if it exists")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    ),
+                clap::Command::new("history")
+                    .about("List previous versions of the configuration file, the newest first"),
+                clap::Command::new("undo")
+                    .about("Revert the last change of the configuration file")
+                    .long_about("Revert the last change of the configuration file. Undo is recorded in history as well, \
+                        so running it again reverts the undo.")
+                    .arg(
+                        Arg::new("dry-run")
+                            .long("dry-run")
+                            .help("Only print presets which would be changed")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    ),
+                clap::Command::new("restore")
+                    .about("Restore version of the configuration file from history")
+                    .arg(
+                        arg!([VERSION])
+                            .required(true)
+                            .help("Version number as printed by 'history' command")
+                            .value_parser(clap::value_parser!(usize))
+                    )
+                    .arg(
+                        Arg::new("dry-run")
+                            .long("dry-run")
+                            .help("Only print presets which would be changed")
+                            .action(ArgAction::SetTrue)
+                            .required(false)
+                    ),
             ])
             .arg(Arg::new("verbose")
                 .short('v')
@@ -312,13 +1360,23 @@ impl Cli {
                 .help("Path to the configuration file")
                 .long_help("Path to the configuration file. When not specified explicitly, \n\
                     '$XDG_CONFIG_HOME/display-presets.json' will be used. \
-                    If environment variable 'XDG_CONFIG_HOME' is not set, '$HOME/.config/display-presets.json' will be used.
+                    If environment variable 'XDG_CONFIG_HOME' is not set, '$HOME/.config/display-presets.json' will be used. \
+                    Existing 'display-presets.toml', 'display-presets.yaml' or 'display-presets.yml' is used instead of a missing JSON file. \
+                    The format is chosen by extension: TOML for '.toml', YAML for '.yaml' and '.yml', JSON otherwise.
                 ")
                 .action(ArgAction::Set))
             .arg(Arg::new("timeout")
                 .short('t')
                 .long("timeout")
                 .help("Timeout (in seconds) for communication with Mutter D-Bus interface. 10 seconds by default")
+                .value_parser(clap::value_parser!(u64))
+                .action(ArgAction::Set)
+            )
+            .arg(Arg::new("history-depth")
+                .long("history-depth")
+                .value_name("N")
+                .help("Number of previous versions of the configuration file kept in history. 10 by default, 0 disables history")
+                .value_parser(clap::value_parser!(usize))
                 .action(ArgAction::Set)
             )
             .get_matches();
@@ -327,10 +1385,38 @@ impl Cli {
             Some(("save", sub_matches)) => Box::new(SaveCommand {
                 name: sub_matches.get_one::<String>("NAME").unwrap().to_string(),
                 force: sub_matches.get_flag("force"),
+                dry_run: sub_matches.get_flag("dry-run"),
             }),
             Some(("apply", sub_matches)) => Box::new(ApplyCommand {
                 name: sub_matches.get_one::<String>("NAME").unwrap().to_string(),
                 persistent: sub_matches.get_flag("persistent"),
+                dry_run: sub_matches.get_flag("dry-run"),
+                strict: sub_matches.get_flag("strict"),
+                confirm_timeout: sub_matches
+                    .get_one::<u64>("confirm-timeout")
+                    .map(|secs| Duration::from_secs(*secs)),
+            }),
+            Some(("validate", sub_matches)) => Box::new(ValidateCommand {
+                name: sub_matches.get_one::<String>("NAME").cloned(),
+                strict: sub_matches.get_flag("strict"),
+            }),
+            Some(("watch", sub_matches)) => Box::new(WatchCommand {
+                debounce: match sub_matches.get_one::<u64>("debounce") {
+                    Some(debounce) => Duration::from_millis(*debounce),
+                    None => defaults::default_debounce(),
+                },
+            }),
+            Some(("auto", sub_matches)) => Box::new(AutoCommand {
+                persistent: sub_matches.get_flag("persistent"),
+                explain: sub_matches.get_flag("explain"),
+            }),
+            Some(("rules", sub_matches)) => Box::new(RulesCommand {
+                name: sub_matches.get_one::<String>("NAME").unwrap().to_string(),
+                rules: parse_rules(sub_matches),
+                clear: sub_matches.get_flag("clear"),
+            }),
+            Some(("doctor", sub_matches)) => Box::new(DoctorCommand {
+                json: sub_matches.get_flag("json"),
             }),
             Some(("list", _)) => Box::new(ListCommand {}),
             Some(("delete", sub_matches)) => Box::new(DeleteCommand {
@@ -347,6 +1433,52 @@ impl Cli {
             Some(("show", sub_matches)) => Box::new(ShowCommand {
                 name: sub_matches.get_one::<String>("NAME").unwrap().to_string(),
             }),
+            Some(("import-monitors-xml", sub_matches)) => Box::new(ImportMonitorsXmlCommand {
+                path: sub_matches.get_one::<String>("PATH").cloned(),
+                name: sub_matches.get_one::<String>("name").cloned(),
+                force: sub_matches.get_flag("force"),
+                dry_run: sub_matches.get_flag("dry-run"),
+            }),
+            Some(("export-monitors-xml", sub_matches)) => Box::new(ExportMonitorsXmlCommand {
+                names: sub_matches
+                    .get_many::<String>("NAME")
+                    .map(|names| names.cloned().collect())
+                    .unwrap_or_default(),
+                output: sub_matches.get_one::<String>("output").cloned(),
+            }),
+            Some(("export", sub_matches)) => Box::new(ExportCommand {
+                names: sub_matches
+                    .get_many::<String>("NAME")
+                    .map(|names| names.cloned().collect())
+                    .unwrap_or_default(),
+                format: match sub_matches.get_one::<String>("format").map(String::as_str) {
+                    Some("kanshi") => Format::Kanshi,
+                    Some("sway") => Format::Sway,
+                    Some("xrandr") => Format::Xrandr,
+                    Some("gnome-monitor-config") => Format::GnomeMonitorConfig,
+                    _ => Format::Preset,
+                },
+                output: sub_matches.get_one::<String>("output").cloned(),
+            }),
+            Some(("import", sub_matches)) => Box::new(ImportCommand {
+                path: sub_matches.get_one::<String>("PATH").unwrap().clone(),
+                name: sub_matches.get_one::<String>("as").cloned(),
+                force: sub_matches.get_flag("force"),
+                dry_run: sub_matches.get_flag("dry-run"),
+            }),
+            Some(("convert", sub_matches)) => Box::new(ConvertCommand {
+                path: sub_matches.get_one::<String>("PATH").unwrap().clone(),
+                force: sub_matches.get_flag("force"),
+            }),
+            Some(("history", _)) => Box::new(HistoryCommand {}),
+            Some(("undo", sub_matches)) => Box::new(RestoreCommand {
+                version: None,
+                dry_run: sub_matches.get_flag("dry-run"),
+            }),
+            Some(("restore", sub_matches)) => Box::new(RestoreCommand {
+                version: sub_matches.get_one::<usize>("VERSION").copied(),
+                dry_run: sub_matches.get_flag("dry-run"),
+            }),
             _ => Err("Unknown command")?,
         };
 
@@ -357,11 +1489,723 @@ impl Cli {
             },
             verbose: matches.get_flag("verbose"),
             timeout: match matches.get_one::<u64>("timeout") {
-                Some(timeout) => Duration::from_secs(timeout.clone()),
+                Some(timeout) => Duration::from_secs(*timeout),
                 None => defaults::default_timeout(),
             },
+            history_depth: match matches.get_one::<usize>("history-depth") {
+                Some(depth) => *depth,
+                None => defaults::default_history_depth(),
+            },
+            command_line: command_line(std::env::args().skip(1)),
         };
 
         Ok(Cli { command, options })
     }
 }
+
+/// Arguments of the process joined into a command line, quoted where needed.
+fn command_line(args: impl Iterator<Item = String>) -> String {
+    args.map(|arg| {
+        if arg.is_empty() || arg.contains(char::is_whitespace) {
+            format!("'{}'", arg)
+        } else {
+            arg
+        }
+    })
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Definition of 'rules' subcommand.
+fn rules_command() -> clap::Command {
+    clap::Command::new("rules")
+        .about("Show or set rules for automatic selection of preset")
+        .long_about("Show or set rules for automatic selection of preset. Rules are printed when no options are given. \
+            Otherwise rules of the preset are replaced with specified ones. Presets without rules are selected \
+            when the same monitors are connected as they were saved with.")
+        .arg(
+            arg!([NAME])
+                .required(true)
+                .help("Preset name")
+        )
+        .arg(
+            Arg::new("priority")
+                .long("priority")
+                .help("Priority of the preset when several presets satisfy their rules. 0 by default")
+                .value_parser(clap::value_parser!(i32))
+                .allow_negative_numbers(true)
+                .action(ArgAction::Set)
+        )
+        .arg(
+            Arg::new("builtin")
+                .long("builtin")
+                .help("Require built-in panel to be present or absent")
+                .value_parser(["present", "absent"])
+                .action(ArgAction::Set)
+        )
+        .arg(
+            Arg::new("vendor")
+                .long("vendor")
+                .help("Require a monitor of the vendor to be connected, e.g. GSM. Can be repeated")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("product")
+                .long("product")
+                .help("Require a monitor of the product to be connected. Can be repeated")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("min-monitors")
+                .long("min-monitors")
+                .help("Require at least specified number of connected monitors")
+                .value_parser(clap::value_parser!(usize))
+                .action(ArgAction::Set)
+        )
+        .arg(
+            Arg::new("max-monitors")
+                .long("max-monitors")
+                .help("Require at most specified number of connected monitors")
+                .value_parser(clap::value_parser!(usize))
+                .action(ArgAction::Set)
+        )
+        .arg(
+            Arg::new("same-monitors")
+                .long("same-monitors")
+                .help("Require the same monitors to be connected as the preset was saved with")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("clear")
+                .long("clear")
+                .help("Remove rules of the preset")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["priority", "builtin", "vendor", "product", "min-monitors", "max-monitors", "same-monitors"])
+        )
+}
+
+fn parse_rules(matches: &clap::ArgMatches) -> Option<SelectionRules> {
+    let mut conditions = Vec::new();
+
+    if let Some(builtin) = matches.get_one::<String>("builtin") {
+        conditions.push(Condition::Builtin {
+            present: builtin == "present",
+        });
+    }
+    if let Some(vendors) = matches.get_many::<String>("vendor") {
+        conditions.extend(vendors.map(|v| Condition::Vendor { vendor: v.clone() }));
+    }
+    if let Some(products) = matches.get_many::<String>("product") {
+        conditions.extend(products.map(|p| Condition::Product { product: p.clone() }));
+    }
+
+    let min = matches.get_one::<usize>("min-monitors").copied();
+    let max = matches.get_one::<usize>("max-monitors").copied();
+    if min.is_some() || max.is_some() {
+        conditions.push(Condition::MonitorCount { min, max });
+    }
+    if matches.get_flag("same-monitors") {
+        conditions.push(Condition::SameMonitors);
+    }
+
+    let priority = matches.get_one::<i32>("priority").copied();
+
+    if conditions.is_empty() && priority.is_none() {
+        return None;
+    }
+    if conditions.is_empty() {
+        // Priority alone keeps the implicit condition of presets without rules, an empty
+        // list of conditions would make the preset eligible for any monitors.
+        conditions.push(Condition::SameMonitors);
+    }
+
+    Some(SelectionRules {
+        priority: priority.unwrap_or_default(),
+        conditions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fake::FakeBackend;
+    use crate::fixtures::{monitor, state};
+    use crate::model::{Configuration, PropValue};
+    use uuid::Uuid;
+
+    fn options(presets: Vec<(&str, DisplayConfigState)>) -> GenericOptions {
+        let config_path = format!("/tmp/{}.json", Uuid::new_v4());
+        let configuration = Configuration {
+            presets: presets
+                .into_iter()
+                .map(|(name, state)| Preset {
+                    name: name.to_string(),
+                    display_config: PresetConfig::from_state(&state),
+                    rules: None,
+                })
+                .collect(),
+        };
+        config_file::write_config(&config_path, &configuration).unwrap();
+
+        GenericOptions {
+            config_path,
+            verbose: false,
+            timeout: Duration::from_secs(1),
+            history_depth: 10,
+            command_line: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn reverts_unconfirmed_preset_with_the_same_method() {
+        let desk = state(vec![monitor("DP-1", "1")]);
+        let mut tv = desk.clone();
+        tv.logical_monitors[0].scale = 2.0;
+        let options = options(vec![("tv", tv)]);
+        let backend = FakeBackend::new(desk.clone());
+
+        let result = ApplyCommand {
+            name: "tv".to_string(),
+            persistent: true,
+            dry_run: false,
+            strict: false,
+            confirm_timeout: Some(Duration::ZERO),
+        }
+        .execute(&options, &backend);
+
+        assert!(result.is_err());
+        let methods: Vec<_> = backend
+            .applied
+            .borrow()
+            .iter()
+            .map(|(method, _)| *method)
+            .filter(|method| *method != ApplyMethod::Verify)
+            .collect();
+        assert_eq!(
+            vec![ApplyMethod::Persistent, ApplyMethod::Persistent],
+            methods
+        );
+        assert_eq!(1.0, backend.state.borrow().logical_monitors[0].scale);
+    }
+
+    #[test]
+    fn priority_alone_keeps_preset_to_its_monitors() {
+        let matches = rules_command().get_matches_from(["rules", "desk", "--priority", "5"]);
+        let desk = state(vec![monitor("DP-1", "1")]);
+        let configuration = Configuration {
+            presets: vec![Preset {
+                name: "desk".to_string(),
+                display_config: PresetConfig::from_state(&desk),
+                rules: parse_rules(&matches),
+            }],
+        };
+
+        let tv = state(vec![monitor("HDMI-1", "3")]);
+        assert!(selection::select(&selection::evaluate(&configuration, &tv)).is_none());
+        assert!(selection::select(&selection::evaluate(&configuration, &desk)).is_some());
+    }
+
+    fn apply(name: &str, dry_run: bool) -> ApplyCommand {
+        ApplyCommand {
+            name: name.to_string(),
+            persistent: false,
+            dry_run,
+            strict: false,
+            confirm_timeout: None,
+        }
+    }
+
+    #[test]
+    fn saves_current_state() {
+        let options = options(vec![]);
+        let backend = FakeBackend::new(state(vec![monitor("DP-1", "1")]));
+
+        SaveCommand {
+            name: "desk".to_string(),
+            force: false,
+            dry_run: false,
+        }
+        .execute(&options, &backend)
+        .unwrap();
+
+        let configuration = config_file::read_config(&options.config_path).unwrap();
+        let preset = configuration.get_preset("desk").unwrap();
+        let monitors: Vec<_> = preset.display_config.monitors().collect();
+        assert_eq!(1, monitors.len());
+        assert_eq!("DP-1", monitors[0].monitor_info.connector);
+        assert!(monitors[0].mode.is_some());
+    }
+
+    #[test]
+    fn save_with_dry_run_keeps_configuration() {
+        let options = options(vec![("desk", state(vec![monitor("DP-1", "1")]))]);
+        let backend = FakeBackend::new(state(vec![monitor("HDMI-1", "3")]));
+        let before = std::fs::read_to_string(&options.config_path).unwrap();
+
+        SaveCommand {
+            name: "desk".to_string(),
+            force: true,
+            dry_run: true,
+        }
+        .execute(&options, &backend)
+        .unwrap();
+
+        assert_eq!(
+            before,
+            std::fs::read_to_string(&options.config_path).unwrap()
+        );
+        assert!(history::read_history(&options.config_path)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn applies_preset_temporarily() {
+        let desk = state(vec![monitor("DP-1", "1"), monitor("DP-2", "2")]);
+        let options = options(vec![("desk", desk)]);
+        let backend = FakeBackend::new(state(vec![monitor("DP-2", "2"), monitor("DP-1", "1")]));
+
+        apply("desk", false).execute(&options, &backend).unwrap();
+
+        let applied = backend.applied.borrow();
+        assert_eq!(1, applied.len());
+        assert_eq!(ApplyMethod::Temporary, applied[0].0);
+        let monitors: Vec<(&str, &str)> = applied[0]
+            .1
+            .logical_monitors
+            .iter()
+            .flat_map(|lm| lm.monitors.iter())
+            .map(|m| (m.connector.as_str(), m.mode_id.as_str()))
+            .collect();
+        assert_eq!(
+            vec![("DP-1", "1920x1080@60"), ("DP-2", "1920x1080@60")],
+            monitors
+        );
+        assert_eq!(2, backend.state.borrow().serial);
+    }
+
+    #[test]
+    fn retries_apply_with_stale_serial() {
+        let desk = state(vec![monitor("DP-1", "1")]);
+        let options = options(vec![("desk", desk.clone())]);
+        let backend = FakeBackend::new(desk);
+        backend
+            .apply_errors
+            .borrow_mut()
+            .push_back(Error::AccessDenied("stale".to_string()));
+
+        apply("desk", false).execute(&options, &backend).unwrap();
+
+        assert_eq!(2, backend.applied.borrow().len());
+        assert_eq!(2, backend.state.borrow().serial);
+    }
+
+    #[test]
+    fn gives_up_retrying_apply() {
+        let desk = state(vec![monitor("DP-1", "1")]);
+        let options = options(vec![("desk", desk.clone())]);
+        let backend = FakeBackend::new(desk);
+        for _ in 0..defaults::default_apply_attempts() {
+            backend
+                .apply_errors
+                .borrow_mut()
+                .push_back(Error::AccessDenied("stale".to_string()));
+        }
+
+        assert!(matches!(
+            apply("desk", false).execute(&options, &backend),
+            Err(Error::AccessDenied(_))
+        ));
+        assert_eq!(
+            defaults::default_apply_attempts() as usize,
+            backend.applied.borrow().len()
+        );
+    }
+
+    #[test]
+    fn refuses_to_apply_when_not_allowed() {
+        let desk = state(vec![monitor("DP-1", "1")]);
+        let options = options(vec![("desk", desk.clone())]);
+        let backend = FakeBackend::new(desk);
+        backend.properties.borrow_mut().insert(
+            "ApplyMonitorsConfigAllowed".to_string(),
+            PropValue::Bool(false),
+        );
+
+        assert!(matches!(
+            apply("desk", false).execute(&options, &backend),
+            Err(Error::ApplyNotAllowed)
+        ));
+        assert!(backend.applied.borrow().is_empty());
+    }
+
+    #[test]
+    fn finds_rotated_builtin_displays() {
+        let mut laptop = monitor("eDP-1", "1");
+        laptop
+            .properties
+            .insert("is-builtin".to_string(), PropValue::Bool(true));
+        let current = state(vec![laptop, monitor("DP-1", "2")]);
+
+        let mut preset = current.clone();
+        for lm in &mut preset.logical_monitors {
+            lm.transform = 1;
+        }
+        let config = resolve::resolve_preset(
+            &PresetConfig::from_state(&preset),
+            &current,
+            &ResolveOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec!["eDP-1"],
+            managed_orientation_conflicts(&config, &current)
+        );
+    }
+
+    #[test]
+    fn undoes_deleted_preset() {
+        let options = options(vec![]);
+        let backend = FakeBackend::new(state(vec![monitor("DP-1", "1")]));
+        let save = |name: &str| SaveCommand {
+            name: name.to_string(),
+            force: false,
+            dry_run: false,
+        };
+        save("desk").execute(&options, &backend).unwrap();
+        save("home").execute(&options, &backend).unwrap();
+        DeleteCommand {
+            name: "desk".to_string(),
+        }
+        .execute(&options, &backend)
+        .unwrap();
+
+        let undo = |dry_run| RestoreCommand {
+            version: None,
+            dry_run,
+        };
+        let preset_names = || {
+            config_file::read_config(&options.config_path)
+                .unwrap()
+                .presets
+                .into_iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>()
+        };
+
+        undo(true).execute(&options, &backend).unwrap();
+        assert_eq!(vec!["home"], preset_names());
+
+        undo(false).execute(&options, &backend).unwrap();
+        assert_eq!(vec!["desk", "home"], preset_names());
+
+        RestoreCommand {
+            version: Some(4),
+            dry_run: false,
+        }
+        .execute(&options, &backend)
+        .unwrap();
+        assert!(preset_names().is_empty());
+    }
+
+    #[test]
+    fn imports_monitors_xml_skipping_duplicates() {
+        let options = options(vec![]);
+        let backend = FakeBackend::new(state(vec![]));
+        let path = format!("/tmp/{}.xml", Uuid::new_v4());
+        std::fs::write(
+            &path,
+            r#"<monitors version="2">
+              <configuration>
+                <logicalmonitor>
+                  <x>0</x><y>0</y><primary>yes</primary>
+                  <monitor>
+                    <monitorspec>
+                      <connector>DP-1</connector><vendor>GSM</vendor>
+                      <product>LG Ultra HD</product><serial>1</serial>
+                    </monitorspec>
+                    <mode><width>1920</width><height>1080</height><rate>60</rate></mode>
+                  </monitor>
+                </logicalmonitor>
+              </configuration>
+            </monitors>"#,
+        )
+        .unwrap();
+        let import = ImportMonitorsXmlCommand {
+            path: Some(path.clone()),
+            name: None,
+            force: false,
+            dry_run: false,
+        };
+
+        import.execute(&options, &backend).unwrap();
+        import.execute(&options, &backend).unwrap();
+
+        let configuration = config_file::read_config(&options.config_path).unwrap();
+        assert_eq!(1, configuration.presets.len());
+        assert_eq!("DP-1", configuration.presets[0].name);
+        assert!(configuration.presets[0]
+            .display_config
+            .same_layout(&PresetConfig::from_state(&state(vec![monitor(
+                "DP-1", "1"
+            )]))));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn numbers_only_imported_presets_given_name() {
+        let options = options(vec![
+            ("desk", state(vec![monitor("DP-1", "1")])),
+            ("work (2)", state(vec![monitor("eDP-1", "9")])),
+        ]);
+        let backend = FakeBackend::new(state(vec![]));
+        let path = format!("/tmp/{}.xml", Uuid::new_v4());
+        let configuration = |connector: &str, serial: &str| {
+            format!(
+                "<configuration><logicalmonitor><x>0</x><y>0</y><primary>yes</primary>\
+                 <monitor><monitorspec><connector>{}</connector><vendor>GSM</vendor>\
+                 <product>LG Ultra HD</product><serial>{}</serial></monitorspec>\
+                 <mode><width>1920</width><height>1080</height><rate>60</rate></mode>\
+                 </monitor></logicalmonitor></configuration>",
+                connector, serial
+            )
+        };
+        std::fs::write(
+            &path,
+            format!(
+                r#"<monitors version="2">{}{}{}</monitors>"#,
+                configuration("DP-1", "1"),
+                configuration("DP-2", "2"),
+                configuration("HDMI-1", "3")
+            ),
+        )
+        .unwrap();
+
+        ImportMonitorsXmlCommand {
+            path: Some(path.clone()),
+            name: Some("work".to_string()),
+            force: false,
+            dry_run: false,
+        }
+        .execute(&options, &backend)
+        .unwrap();
+
+        let configuration = config_file::read_config(&options.config_path).unwrap();
+        let names: Vec<_> = configuration.presets.iter().map(|p| &p.name).collect();
+        assert_eq!(vec!["desk", "work (2)", "work", "work (3)"], names);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn exports_presets_to_monitors_xml() {
+        let options = options(vec![
+            (
+                "desk",
+                state(vec![monitor("DP-1", "1"), monitor("DP-2", "2")]),
+            ),
+            ("tv", state(vec![monitor("HDMI-1", "3")])),
+        ]);
+        let backend = FakeBackend::new(state(vec![]));
+        let path = format!("/tmp/{}.xml", Uuid::new_v4());
+        let export = |names: &[&str]| ExportMonitorsXmlCommand {
+            names: names.iter().map(|n| n.to_string()).collect(),
+            output: Some(path.clone()),
+        };
+
+        export(&["desk"]).execute(&options, &backend).unwrap();
+        export(&[]).execute(&options, &backend).unwrap();
+
+        let exported = monitors_xml::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(2, exported.len());
+        assert!(
+            exported[0].same_layout(&PresetConfig::from_state(&state(vec![
+                monitor("DP-1", "1"),
+                monitor("DP-2", "2")
+            ])))
+        );
+        assert!(matches!(
+            export(&["missing"]).execute(&options, &backend),
+            Err(Error::PresetNotFound(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn exports_single_preset_as_executable_script() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let options = options(vec![
+            ("desk", state(vec![monitor("DP-1", "1")])),
+            ("tv", state(vec![monitor("HDMI-1", "3")])),
+        ]);
+        let backend = FakeBackend::new(state(vec![]));
+        let path = format!("/tmp/{}.sh", Uuid::new_v4());
+        let export = |names: &[&str]| ExportCommand {
+            names: names.iter().map(|n| n.to_string()).collect(),
+            format: Format::Xrandr,
+            output: Some(path.clone()),
+        };
+
+        assert!(export(&[]).execute(&options, &backend).is_err());
+        export(&["desk"]).execute(&options, &backend).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o755, mode & 0o777);
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("--output 'DP-1'"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn imports_exported_preset() {
+        let options = options(vec![
+            ("desk", state(vec![monitor("DP-1", "1")])),
+            ("tv", state(vec![monitor("HDMI-1", "3")])),
+        ]);
+        let backend = FakeBackend::new(state(vec![]));
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+        ExportCommand {
+            names: vec!["desk".to_string()],
+            format: Format::Preset,
+            output: Some(path.clone()),
+        }
+        .execute(&options, &backend)
+        .unwrap();
+        let import = |name: Option<&str>, force: bool| ImportCommand {
+            path: path.clone(),
+            name: name.map(String::from),
+            force,
+            dry_run: false,
+        };
+
+        assert!(matches!(
+            import(Some("tv"), false).execute(&options, &backend),
+            Err(Error::PresetExists(_))
+        ));
+        import(Some("tv"), true)
+            .execute(&options, &backend)
+            .unwrap();
+        import(Some("office"), false)
+            .execute(&options, &backend)
+            .unwrap();
+
+        let configuration = config_file::read_config(&options.config_path).unwrap();
+        let desk = &configuration.get_preset("desk").unwrap().display_config;
+        assert_eq!(3, configuration.presets.len());
+        assert_eq!(
+            desk,
+            &configuration.get_preset("tv").unwrap().display_config
+        );
+        assert_eq!(
+            desk,
+            &configuration.get_preset("office").unwrap().display_config
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn converts_configuration() {
+        let options = options(vec![("desk", state(vec![monitor("DP-1", "1")]))]);
+        let backend = FakeBackend::new(state(vec![]));
+        let path = format!("/tmp/{}.toml", Uuid::new_v4());
+        let convert = |force: bool| ConvertCommand {
+            path: path.clone(),
+            force,
+        };
+
+        convert(false).execute(&options, &backend).unwrap();
+        assert!(convert(false).execute(&options, &backend).is_err());
+        convert(true).execute(&options, &backend).unwrap();
+
+        assert_eq!(
+            config_file::read_config(&options.config_path).unwrap(),
+            config_file::read_config(&path).unwrap()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn quotes_command_line() {
+        assert_eq!(
+            "save 'my desk' --force",
+            command_line(["save", "my desk", "--force"].into_iter().map(String::from))
+        );
+    }
+
+    #[test]
+    fn fails_to_apply_unknown_preset() {
+        let options = options(vec![]);
+        let backend = FakeBackend::new(state(vec![monitor("DP-1", "1")]));
+
+        assert!(matches!(
+            apply("desk", false).execute(&options, &backend),
+            Err(Error::PresetNotFound(_))
+        ));
+        assert!(backend.applied.borrow().is_empty());
+    }
+
+    #[test]
+    fn dry_run_only_verifies() {
+        let desk = state(vec![monitor("DP-1", "1")]);
+        let options = options(vec![("desk", desk.clone())]);
+        let backend = FakeBackend::new(desk);
+
+        apply("desk", true).execute(&options, &backend).unwrap();
+
+        let applied = backend.applied.borrow();
+        assert_eq!(1, applied.len());
+        assert_eq!(ApplyMethod::Verify, applied[0].0);
+        assert_eq!(1, backend.state.borrow().serial);
+    }
+
+    #[test]
+    fn rejected_presets_exit_with_code_of_rejection() {
+        use crate::mutter::Rejection;
+
+        let desk = state(vec![monitor("DP-1", "1")]);
+        let options = options(vec![("desk", desk.clone())]);
+        let backend = FakeBackend::new(desk);
+        let validate = ValidateCommand {
+            name: None,
+            strict: false,
+        };
+        let reject = |rejection: Rejection| {
+            backend
+                .apply_errors
+                .borrow_mut()
+                .push_back(Error::Rejected(rejection));
+        };
+
+        reject(Rejection::InvalidArgs("bad mode".to_string()));
+        let error = apply("desk", true).execute(&options, &backend).unwrap_err();
+        assert_eq!(10, error.exit_code());
+
+        reject(Rejection::LimitsExceeded("too large".to_string()));
+        let error = validate.execute(&options, &backend).unwrap_err();
+        assert_eq!(11, error.exit_code());
+
+        backend.state.borrow_mut().monitors.clear();
+        let error = validate.execute(&options, &backend).unwrap_err();
+        assert_eq!(7, error.exit_code());
+    }
+
+    #[test]
+    fn watch_applies_matching_preset_on_hotplug() {
+        let desk = state(vec![monitor("eDP-1", "1"), monitor("DP-1", "2")]);
+        let options = options(vec![("desk", desk.clone())]);
+        let backend = FakeBackend::new(state(vec![monitor("eDP-1", "1")]));
+        backend.hotplug.borrow_mut().push_back(desk);
+
+        WatchCommand {
+            debounce: Duration::from_millis(0),
+        }
+        .execute(&options, &backend)
+        .unwrap();
+
+        let applied = backend.applied.borrow();
+        assert_eq!(1, applied.len());
+        assert_eq!(ApplyMethod::Temporary, applied[0].0);
+    }
+}
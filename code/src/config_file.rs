@@ -1,15 +1,110 @@
-use std::path::Path;
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
 
-use log::debug;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
 
-use crate::model::Configuration;
+use crate::config_format::ConfigFormat;
+use crate::error::Error;
+use crate::migrations::{self, CONFIG_VERSION};
+use crate::model::{Configuration, Preset};
 
-fn create_empty_config_file(path: &String) -> Result<(), Box<dyn std::error::Error>> {
+/// Identifies documents with a single exported preset.
+const PRESET_DOCUMENT_FORMAT: &str = "mutter-display-presets/preset";
+/// Version of the preset document. Increased when older versions can't read new documents.
+pub const PRESET_DOCUMENT_VERSION: u32 = 2;
+
+/// Self-contained document with a single preset, for sharing presets between machines.
+#[derive(Serialize, Deserialize)]
+struct PresetDocument<P> {
+    format: String,
+    version: u32,
+    preset: P,
+}
+
+/// Version of the configuration file, read before the rest of the content.
+#[derive(Deserialize)]
+struct VersionHeader {
+    /// Files written before the version was introduced don't have it.
+    #[serde(default)]
+    version: u32,
+}
+
+/// Configuration as written to the file, with its version first.
+#[derive(Serialize)]
+struct VersionedConfiguration<'a> {
+    version: u32,
+    #[serde(flatten)]
+    configuration: &'a Configuration,
+}
+
+/// Exclusive advisory lock of the configuration file. Released when dropped.
+pub struct ConfigLock {
+    _file: File,
+}
+
+/// Locks the configuration file for read-modify-write cycle, waiting for other
+/// processes holding the lock. The lock is taken on a separate `.lock` file, because
+/// the configuration file itself is replaced on every write.
+pub fn lock_config(path: &str) -> Result<ConfigLock, Error> {
+    debug!("Waiting for lock of {}", path);
+    let lock = flock_config(path, libc::LOCK_EX)?.expect("blocking lock is always taken");
+    debug!("Lock of {} acquired", path);
+    Ok(lock)
+}
+
+/// Locks the configuration file like [lock_config], unless the lock is held already,
+/// including by this process.
+fn try_lock_config(path: &str) -> Result<Option<ConfigLock>, Error> {
+    flock_config(path, libc::LOCK_EX | libc::LOCK_NB)
+}
+
+fn flock_config(path: &str, operation: libc::c_int) -> Result<Option<ConfigLock>, Error> {
+    let lock_path = lock_path(path);
+    let io_error = |source| Error::ConfigIo {
+        path: lock_path.clone(),
+        source,
+    };
+
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(io_error)?;
+
+    // SAFETY: the descriptor belongs to `file`, which is open for the whole call.
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+        let error = std::io::Error::last_os_error();
+        if error.kind() == std::io::ErrorKind::WouldBlock {
+            return Ok(None);
+        }
+        Err(io_error(error))?
+    }
+
+    Ok(Some(ConfigLock { _file: file }))
+}
+
+/// Path of the lock file next to the target of the configuration file, so that every
+/// path to the same file, e.g. through a symlink, takes the same lock.
+fn lock_path(path: &str) -> String {
+    let target = match std::fs::canonicalize(path) {
+        Ok(target) => target.to_string_lossy().to_string(),
+        Err(_) => path.to_string(),
+    };
+    format!("{}.lock", target)
+}
+
+fn create_empty_config_file(path: &String) -> Result<(), Error> {
     write_config(path, &Configuration { presets: vec![] })?;
     Ok(())
 }
 
-pub fn read_config(path: &String) -> Result<Configuration, Box<dyn std::error::Error>> {
+/// Reads the configuration file, creating an empty one if it's missing. A file of an
+/// older version is upgraded in place first, see [upgrade_config].
+pub fn read_config(path: &String) -> Result<Configuration, Error> {
     let config_path = Path::new(path);
     if !config_path.exists() {
         debug!(
@@ -19,31 +114,243 @@ pub fn read_config(path: &String) -> Result<Configuration, Box<dyn std::error::E
         create_empty_config_file(path)?;
     }
 
-    let config_str = std::fs::read_to_string(path)?;
+    if config_version(path, &read_config_file(path)?)? < CONFIG_VERSION {
+        upgrade_config(path)?;
+    }
 
-    let conf: Configuration = serde_json::from_str(config_str.as_str())?;
+    let conf = load_config(path)?;
     debug!("Configuration retrieved from file {}.", path);
 
     Ok(conf)
 }
 
-pub fn write_config(path: &String, conf: &Configuration) -> Result<(), Box<dyn std::error::Error>> {
-    let conf_json = serde_json::to_string(&conf)?;
+/// Rewrites a file of an older version in the current version, keeping the original as
+/// a backup. When another command holds the lock, the upgrade is left to its write.
+fn upgrade_config(path: &String) -> Result<(), Error> {
+    let Some(_lock) = try_lock_config(path)? else {
+        debug!("Configuration file {} is locked, not upgrading it", path);
+        return Ok(());
+    };
+
+    // Another command may have upgraded the file before the lock was taken.
+    let content = read_config_file(path)?;
+    if config_version(path, &content)? < CONFIG_VERSION {
+        write_config(path, &load_config(path)?)?;
+    }
+    Ok(())
+}
+
+/// Reads configuration of an existing file without modifying it. Configuration of an
+/// older version is migrated in memory.
+pub fn load_config(path: &String) -> Result<Configuration, Error> {
+    let config_str = read_config_file(path)?;
+    let version = config_version(path, &config_str)?;
+
+    if version > CONFIG_VERSION {
+        Err(Error::ConfigVersion {
+            path: path.clone(),
+            version,
+            supported: CONFIG_VERSION,
+        })?
+    } else if version < CONFIG_VERSION {
+        migrate_config(path, &config_str, version)
+    } else {
+        ConfigFormat::of(path).parse(path, &config_str)
+    }
+}
+
+fn config_version(path: &str, content: &str) -> Result<u32, Error> {
+    Ok(ConfigFormat::of(path)
+        .parse::<VersionHeader>(path, content)?
+        .version)
+}
+
+fn read_config_file(path: &String) -> Result<String, Error> {
+    std::fs::read_to_string(path).map_err(|source| Error::ConfigIo {
+        path: path.clone(),
+        source,
+    })
+}
+
+/// Reads configuration of an older version, upgrading it to [CONFIG_VERSION].
+fn migrate_config(path: &String, content: &str, version: u32) -> Result<Configuration, Error> {
+    let mut value: serde_json::Value = ConfigFormat::of(path).parse(path, content)?;
+    migrations::migrate(&mut value, version).map_err(|e| {
+        format!(
+            "Can't upgrade configuration file '{}' from version {}: {}",
+            path, version, e
+        )
+    })?;
+
+    serde_json::from_value(value).map_err(|e| Error::config_parse(path, &e))
+}
+
+/// Keeps a backup of the file of an older version next to it before it's overwritten
+/// by the current version.
+fn backup_older_version(path: &String, content: &str) -> Result<(), Error> {
+    let version = match ConfigFormat::of(path).parse::<VersionHeader>(path, content) {
+        Ok(header) if header.version < CONFIG_VERSION => header.version,
+        _ => return Ok(()),
+    };
+
+    let backup_path = format!("{}.v{}.bak", path, version);
+    std::fs::write(&backup_path, content).map_err(|source| Error::ConfigIo {
+        path: backup_path.clone(),
+        source,
+    })?;
+
+    info!(
+        "Configuration file {} upgraded from version {} to {}, original kept in {}",
+        path, version, CONFIG_VERSION, backup_path
+    );
+    Ok(())
+}
+
+/// Writes configuration to a temporary file next to the configuration file and renames
+/// it over the configuration file, so that the file is never left partially written.
+/// The format is chosen by extension of the file, comments of the existing file are kept
+/// where the format allows. The original of a file of an older version is kept as a
+/// backup, see [backup_older_version].
+pub fn write_config(path: &String, conf: &Configuration) -> Result<(), Error> {
+    let existing = std::fs::read_to_string(path).ok();
+    let content = config_content(path, conf, existing.as_deref())?;
+    if let Some(existing) = &existing {
+        backup_older_version(path, existing)?;
+    }
 
     debug!("Saving configuration to file {}", path);
 
-    std::fs::write(path, conf_json)?;
+    replace_file(Path::new(path), content.as_bytes()).map_err(|source| Error::ConfigIo {
+        path: path.clone(),
+        source,
+    })?;
 
     debug!("Configuration saved to file {}", path);
 
     Ok(())
 }
 
+fn config_content(
+    path: &str,
+    conf: &Configuration,
+    existing: Option<&str>,
+) -> Result<String, Error> {
+    let content = ConfigFormat::of(path).serialize(
+        &VersionedConfiguration {
+            version: CONFIG_VERSION,
+            configuration: conf,
+        },
+        existing,
+    )?;
+    Ok(content)
+}
+
+/// Rewrites configuration file `from` into file `to` in the format of its extension.
+/// Nothing is written when the configuration can't be read back unchanged from the new
+/// content. Returns the number of converted presets.
+pub fn convert_config(from: &String, to: &String) -> Result<usize, Error> {
+    let conf = load_config(from)?;
+    let content = config_content(to, &conf, None)?;
+
+    let converted: Configuration = ConfigFormat::of(to).parse(to, &content)?;
+    if converted != conf {
+        Err(format!(
+            "Configuration from '{}' can't be converted to '{}' without loss",
+            from, to
+        ))?
+    }
+
+    replace_file(Path::new(to), content.as_bytes()).map_err(|source| Error::ConfigIo {
+        path: to.clone(),
+        source,
+    })?;
+    Ok(conf.presets.len())
+}
+
+/// Serializes the preset into a preset document.
+pub fn preset_document(preset: &Preset) -> String {
+    let document = PresetDocument {
+        format: PRESET_DOCUMENT_FORMAT.to_string(),
+        version: PRESET_DOCUMENT_VERSION,
+        preset,
+    };
+
+    serde_json::to_string_pretty(&document).unwrap() + "\n"
+}
+
+/// Reads a preset from a preset document. `source` names the document in errors.
+pub fn parse_preset_document(content: &str, source: &str) -> Result<Preset, Error> {
+    let invalid = |message: String| Error::InvalidPreset {
+        source: source.to_string(),
+        message,
+    };
+    let document: PresetDocument<serde_json::Value> =
+        serde_json::from_str(content).map_err(|e| invalid(e.to_string()))?;
+
+    if document.format != PRESET_DOCUMENT_FORMAT {
+        Err(invalid(format!("Unknown format '{}'", document.format)))?
+    }
+    if document.version > PRESET_DOCUMENT_VERSION {
+        Err(invalid(format!(
+            "Document version {} is newer than supported version {}. Upgrade the application",
+            document.version, PRESET_DOCUMENT_VERSION
+        )))?
+    }
+
+    let mut preset = document.preset;
+    if document.version < PRESET_DOCUMENT_VERSION {
+        migrations::desired_preset_configuration(&mut preset).map_err(invalid)?;
+    }
+
+    serde_json::from_value(preset).map_err(|e| invalid(e.to_string()))
+}
+
+/// Atomically replaces content of the file, see [write_config].
+pub fn replace_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    // Replace the target of a symlink, not the symlink itself.
+    let path = match std::fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(_) => path.to_path_buf(),
+    };
+    let permissions = std::fs::metadata(&path).ok().map(|m| m.permissions());
+    let temp_path = temp_path(&path);
+
+    let write_temp = || -> std::io::Result<()> {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content)?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.sync_all()?;
+        std::fs::rename(&temp_path, &path)
+    };
+
+    if let Err(e) = write_temp() {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    // Persist the rename itself.
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()))
+}
+
 #[cfg(test)]
 mod tests {
     use uuid::Uuid;
 
-    use crate::model::{DisplayConfigState, Preset, Props};
+    use crate::model::{Preset, PresetConfig, SelectionRules};
 
     use super::*;
 
@@ -51,19 +358,15 @@ mod tests {
         Configuration {
             presets: vec![Preset {
                 name: "TestPreset".to_string(),
-                display_config: DisplayConfigState {
-                    serial: 1u32,
-                    monitors: vec![],
-                    logical_monitors: vec![],
-                    properties: Props::new(),
-                },
+                display_config: PresetConfig::default(),
+                rules: None,
             }],
         }
     }
 
     #[test]
     fn creates_empty_config_if_absent_on_read_attempt() {
-        let path = format!("/tmp/{}.json", Uuid::new_v4().to_string());
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
         let configuration = read_config(&path).unwrap();
 
         assert_eq!(configuration, Configuration { presets: vec![] })
@@ -71,7 +374,7 @@ mod tests {
 
     #[test]
     fn reads_existing_config() {
-        let path = format!("/tmp/{}.json", Uuid::new_v4().to_string());
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
 
         let content = r#"
         {
@@ -98,15 +401,227 @@ mod tests {
 
     #[test]
     fn writes_config() {
-        let path = format!("/tmp/{}.json", Uuid::new_v4().to_string());
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
 
         write_config(&path, &get_test_configuration()).expect("Error writing configuration");
 
-        let content = std::fs::read_to_string(path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+
+        assert!(content.starts_with(&format!("{{\n  \"version\": {},\n", CONFIG_VERSION)));
+        assert_eq!(get_test_configuration(), read_config(&path).unwrap());
+    }
 
+    #[test]
+    fn writes_toml_config_keeping_comments() {
+        let path = format!("/tmp/{}.toml", Uuid::new_v4());
+        write_config(&path, &get_test_configuration()).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with(&format!("version = {}\n", CONFIG_VERSION)));
+
+        let commented = content.replace("[[presets]]", "# Laptop only\n[[presets]]");
+        std::fs::write(&path, &commented).unwrap();
+        let mut configuration = read_config(&path).unwrap();
+        configuration.presets[0].rules = Some(SelectionRules {
+            priority: 1,
+            conditions: vec![],
+        });
+        write_config(&path, &configuration).unwrap();
+
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("# Laptop only\n[[presets]]"));
+        assert_eq!(configuration, read_config(&path).unwrap());
+    }
+
+    #[test]
+    fn converts_config_between_formats() {
+        let configuration = crate::fixtures::configuration();
+        let json = format!("/tmp/{}.json", Uuid::new_v4());
+        let toml = format!("/tmp/{}.toml", Uuid::new_v4());
+        let yaml = format!("/tmp/{}.yaml", Uuid::new_v4());
+        let converted = format!("/tmp/{}.json", Uuid::new_v4());
+        write_config(&json, &configuration).unwrap();
+
+        assert_eq!(2, convert_config(&json, &toml).unwrap());
+        assert_eq!(2, convert_config(&toml, &yaml).unwrap());
+        assert_eq!(2, convert_config(&yaml, &converted).unwrap());
+
+        assert_eq!(configuration, read_config(&converted).unwrap());
+    }
+
+    const UNVERSIONED_CONFIG: &str = r#"{"presets": [{"name": "TestPreset", "display_config":
+        {"serial": 1, "monitors": [], "logical_monitors": [], "properties": {}}}]}"#;
+
+    #[test]
+    fn upgrades_unversioned_config_on_read_with_backup() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+        std::fs::write(&path, UNVERSIONED_CONFIG).unwrap();
+
+        let configuration = read_config(&path).unwrap();
+
+        assert_eq!(get_test_configuration(), configuration);
         assert_eq!(
-            content,
-            serde_json::to_string(&get_test_configuration()).unwrap()
+            UNVERSIONED_CONFIG,
+            std::fs::read_to_string(format!("{}.v0.bak", path)).unwrap()
         );
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains(&format!("\"version\": {}", CONFIG_VERSION)));
+        assert_eq!(configuration, read_config(&path).unwrap());
+    }
+
+    #[test]
+    fn leaves_upgrade_of_locked_config_to_its_write() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+        std::fs::write(&path, UNVERSIONED_CONFIG).unwrap();
+        let _lock = lock_config(&path).unwrap();
+
+        let configuration = read_config(&path).unwrap();
+        assert_eq!(get_test_configuration(), configuration);
+        assert_eq!(UNVERSIONED_CONFIG, std::fs::read_to_string(&path).unwrap());
+
+        write_config(&path, &configuration).unwrap();
+
+        assert_eq!(
+            UNVERSIONED_CONFIG,
+            std::fs::read_to_string(format!("{}.v0.bak", path)).unwrap()
+        );
+    }
+
+    #[test]
+    fn refuses_config_of_newer_version() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+        let content = format!(r#"{{"version": {}, "presets": []}}"#, CONFIG_VERSION + 1);
+        std::fs::write(&path, &content).unwrap();
+
+        assert!(matches!(
+            read_config(&path),
+            Err(Error::ConfigVersion { version, .. }) if version == CONFIG_VERSION + 1
+        ));
+        assert_eq!(content, std::fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn preserves_permissions_on_write() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = format!("/tmp/{}", Uuid::new_v4());
+        std::fs::create_dir(&dir).unwrap();
+        let path = format!("{}/presets.json", dir);
+        std::fs::write(&path, format!(r#"{{"version": {}}}"#, CONFIG_VERSION)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        write_config(&path, &get_test_configuration()).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+        assert_eq!(
+            vec!["presets.json".to_string()],
+            std::fs::read_dir(&dir)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn writes_through_symlink() {
+        let target = format!("/tmp/{}.json", Uuid::new_v4());
+        let link = format!("/tmp/{}.json", Uuid::new_v4());
+        std::fs::write(&target, "{}").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_config(&link, &get_test_configuration()).unwrap();
+
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(get_test_configuration(), read_config(&target).unwrap());
+    }
+
+    #[test]
+    fn reads_exported_preset_document() {
+        let preset = get_test_configuration().presets.remove(0);
+
+        let document = preset_document(&preset);
+
+        assert!(document.contains(&format!("\"version\": {}", PRESET_DOCUMENT_VERSION)));
+        assert_eq!(preset, parse_preset_document(&document, "-").unwrap());
+    }
+
+    #[test]
+    fn reads_preset_document_with_captured_state() {
+        let document = r#"{
+            "format": "mutter-display-presets/preset",
+            "version": 1,
+            "preset": {
+                "name": "desk",
+                "display_config": {
+                    "serial": 3,
+                    "monitors": [{
+                        "monitor_info": {"connector": "HDMI-1", "vendor": "SAM", "product": "TV", "serial": "1"},
+                        "modes": [],
+                        "properties": {}
+                    }],
+                    "logical_monitors": [],
+                    "properties": {}
+                }
+            }
+        }"#;
+
+        let preset = parse_preset_document(document, "desk.json").unwrap();
+
+        assert!(preset.display_config.logical_monitors.is_empty());
+        assert_eq!(None, preset.display_config.layout_mode);
+        assert_eq!(
+            "HDMI-1",
+            preset.display_config.disabled_monitors[0].connector
+        );
+    }
+
+    #[test]
+    fn rejects_newer_preset_document() {
+        let document = format!(
+            r#"{{"format": "mutter-display-presets/preset", "version": {}, "preset": {{}}}}"#,
+            PRESET_DOCUMENT_VERSION + 1
+        );
+
+        match parse_preset_document(&document, "desk.json") {
+            Err(Error::InvalidPreset { source, message }) => {
+                assert_eq!("desk.json", source);
+                assert!(message.contains("newer"));
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    fn try_lock(file: &File) -> bool {
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+    }
+
+    #[test]
+    fn lock_is_exclusive() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+
+        let lock = lock_config(&path).unwrap();
+        let other = File::open(format!("{}.lock", path)).unwrap();
+        assert!(!try_lock(&other));
+
+        drop(lock);
+        assert!(try_lock(&other));
+    }
+
+    #[test]
+    fn locks_target_of_symlink() {
+        let target = format!("/tmp/{}.json", Uuid::new_v4());
+        let link = format!("/tmp/{}.json", Uuid::new_v4());
+        std::fs::write(&target, "{}").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let _lock = lock_config(&link).unwrap();
+
+        assert!(!try_lock(&File::open(format!("{}.lock", target)).unwrap()));
+        assert!(!Path::new(&format!("{}.lock", link)).exists());
     }
 }
@@ -0,0 +1,277 @@
+//! Formats of the configuration file, chosen by its extension.
+
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Format of the file: `.toml` files are TOML, `.yaml` and `.yml` files are YAML,
+    /// any other file is JSON.
+    pub fn of(path: &str) -> ConfigFormat {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    /// Deserializes content of the file at `path`. Errors report the location in the file.
+    pub fn parse<T: DeserializeOwned>(&self, path: &str, content: &str) -> Result<T, Error> {
+        match self {
+            ConfigFormat::Json => {
+                serde_json::from_str(content).map_err(|e| Error::config_parse(path, &e))
+            }
+            ConfigFormat::Toml => toml::from_str(content).map_err(|e| {
+                let (line, column) = e
+                    .span()
+                    .map(|span| line_column(content, span.start))
+                    .unwrap_or_default();
+                Error::config_parse_at(path, line, column, e.message().to_string())
+            }),
+            ConfigFormat::Yaml => {
+                let deserializer = serde_yaml::Deserializer::from_str(content);
+                serde_yaml::with::singleton_map_recursive::deserialize(deserializer).map_err(|e| {
+                    let (line, column) = e
+                        .location()
+                        .map(|l| (l.line(), l.column()))
+                        .unwrap_or_default();
+                    Error::config_parse_at(path, line, column, e.to_string())
+                })
+            }
+        }
+    }
+
+    /// Serializes the value for people to read and edit. Comments of `existing` content
+    /// are kept: TOML comments of keys and tables which are still present, YAML comment
+    /// at the beginning of the file. JSON has no comments.
+    pub fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        existing: Option<&str>,
+    ) -> Result<String, String> {
+        match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(value)
+                .map(|content| content + "\n")
+                .map_err(|e| e.to_string()),
+            ConfigFormat::Toml => {
+                let content = toml::to_string_pretty(value).map_err(|e| e.to_string())?;
+
+                match existing.and_then(|e| e.parse::<DocumentMut>().ok()) {
+                    Some(old) => {
+                        let mut document: DocumentMut = content
+                            .parse()
+                            .map_err(|e: toml_edit::TomlError| e.to_string())?;
+                        copy_comments(old.as_table(), document.as_table_mut());
+                        document.set_trailing(old.trailing().clone());
+                        Ok(document.to_string())
+                    }
+                    None => Ok(content),
+                }
+            }
+            ConfigFormat::Yaml => {
+                // Enums are written as maps rather than YAML tags, which are hard to edit.
+                let mut content = Vec::new();
+                serde_yaml::with::singleton_map_recursive::serialize(
+                    value,
+                    &mut serde_yaml::Serializer::new(&mut content),
+                )
+                .map_err(|e| e.to_string())?;
+                let content = String::from_utf8(content).map_err(|e| e.to_string())?;
+
+                Ok(existing.map(header_comment).unwrap_or_default() + &content)
+            }
+        }
+    }
+}
+
+/// Copies comments of keys and tables of `old` to the same keys and tables of `new`.
+/// Tables in arrays, e.g. presets, are matched by their name, or by position when they
+/// don't have a name.
+fn copy_comments(old: &Table, new: &mut Table) {
+    *new.decor_mut() = old.decor().clone();
+
+    for (mut key, item) in new.iter_mut() {
+        let Some((old_key, old_item)) = old.get_key_value(key.get()) else {
+            continue;
+        };
+        *key.leaf_decor_mut() = old_key.leaf_decor().clone();
+
+        match (old_item, item) {
+            (Item::Table(old), Item::Table(new)) => copy_comments(old, new),
+            (Item::ArrayOfTables(old), Item::ArrayOfTables(new)) => {
+                for (i, table) in new.iter_mut().enumerate() {
+                    let matching = match name(table) {
+                        Some(name) => old.iter().find(|t| self::name(t) == Some(name.clone())),
+                        None => old.get(i),
+                    };
+                    if let Some(old) = matching {
+                        copy_comments(old, table);
+                    }
+                }
+            }
+            (Item::Value(old), Item::Value(new)) => *new.decor_mut() = old.decor().clone(),
+            _ => {}
+        }
+    }
+}
+
+fn name(table: &Table) -> Option<String> {
+    table.get("name").and_then(Item::as_str).map(String::from)
+}
+
+/// Comment lines at the beginning of the content.
+fn header_comment(content: &str) -> String {
+    content
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+/// Line and column, both starting at 1, of the byte offset in the content.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::model::{Condition, Configuration, Preset, PresetConfig, SelectionRules};
+
+    fn configuration() -> Configuration {
+        Configuration {
+            presets: ["desk", "tv"]
+                .iter()
+                .map(|name| Preset {
+                    name: name.to_string(),
+                    display_config: PresetConfig::default(),
+                    rules: Some(SelectionRules {
+                        priority: 1,
+                        conditions: vec![Condition::SameMonitors],
+                    }),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn chooses_format_by_extension() {
+        assert_eq!(ConfigFormat::Json, ConfigFormat::of("presets.json"));
+        assert_eq!(
+            ConfigFormat::Toml,
+            ConfigFormat::of("/home/user/presets.TOML")
+        );
+        assert_eq!(ConfigFormat::Yaml, ConfigFormat::of("presets.yml"));
+        assert_eq!(ConfigFormat::Json, ConfigFormat::of("presets"));
+    }
+
+    #[test]
+    fn round_trips_in_every_format() {
+        let configuration = fixtures::configuration();
+
+        for format in [ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml] {
+            let content = format.serialize(&configuration, None).unwrap();
+
+            assert!(content.lines().count() > 2, "{:?} is not pretty", format);
+            assert_eq!(
+                configuration,
+                format.parse::<Configuration>("presets", &content).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn converts_through_every_format() {
+        let configuration = fixtures::configuration();
+        let formats = [
+            ConfigFormat::Json,
+            ConfigFormat::Toml,
+            ConfigFormat::Yaml,
+            ConfigFormat::Json,
+        ];
+
+        let converted = formats.windows(2).fold(
+            formats[0].serialize(&configuration, None).unwrap(),
+            |content, pair| {
+                let parsed: Configuration = pair[0].parse("presets", &content).unwrap();
+                pair[1].serialize(&parsed, None).unwrap()
+            },
+        );
+
+        assert_eq!(
+            configuration,
+            ConfigFormat::Json
+                .parse::<Configuration>("presets.json", &converted)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn keeps_toml_comments() {
+        let existing = "# My presets\n\n\
+            # Desk at work\n\
+            [[presets]]\n\
+            name = \"desk\" # the big one\n";
+
+        let content = ConfigFormat::Toml
+            .serialize(&configuration(), Some(existing))
+            .unwrap();
+
+        assert!(content.starts_with("# My presets\n\n# Desk at work\n[[presets]]\n"));
+        assert!(content.contains("name = \"desk\" # the big one\n"));
+        assert_eq!(
+            configuration(),
+            ConfigFormat::Toml
+                .parse::<Configuration>("presets.toml", &content)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn keeps_only_yaml_header_comment() {
+        let content = ConfigFormat::Yaml
+            .serialize(
+                &configuration(),
+                Some("# My presets\npresets: [] # none yet\n"),
+            )
+            .unwrap();
+
+        assert!(content.starts_with("# My presets\npresets:\n"));
+        assert!(!content.contains("none yet"));
+    }
+
+    #[test]
+    fn reports_error_location() {
+        let error = ConfigFormat::Toml
+            .parse::<Configuration>("presets.toml", "[[presets]]\nname = 1\n")
+            .unwrap_err();
+
+        match error {
+            Error::ConfigParse { line, column, .. } => assert_eq!((2, 8), (line, column)),
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+}
@@ -1,16 +1,43 @@
 use std::time::Duration;
 
 const DEFAULT_CONFIG_FILE_NAME: &str = "display-presets.json";
+/// Names of the configuration file in other supported formats, used when they exist.
+const CONFIG_FILE_NAMES: [&str; 4] = [
+    DEFAULT_CONFIG_FILE_NAME,
+    "display-presets.toml",
+    "display-presets.yaml",
+    "display-presets.yml",
+];
+const MONITORS_XML_FILE_NAME: &str = "monitors.xml";
 
 pub fn default_timeout() -> Duration {
     Duration::from_secs(10)
 }
 
+pub fn default_debounce() -> Duration {
+    Duration::from_millis(1000)
+}
+
+/// How many times configuration is sent to Mutter when it was based on stale state.
+pub fn default_apply_attempts() -> u32 {
+    3
+}
+
+/// Delay before the first retry of applying configuration. Doubled for every next retry.
+pub fn default_retry_backoff() -> Duration {
+    Duration::from_millis(100)
+}
+
+/// How many previous versions of the configuration file are kept.
+pub fn default_history_depth() -> usize {
+    10
+}
+
 pub fn default_config_file_path() -> String {
     if let Ok(xdg_config_home) = std::env::var("XDG_CONFIG_HOME") {
-        format!("{}/{}", xdg_config_home, DEFAULT_CONFIG_FILE_NAME)
+        config_file_in(&xdg_config_home)
     } else if let Ok(home) = std::env::var("HOME") {
-        format!("{}/.config/{}", home, DEFAULT_CONFIG_FILE_NAME)
+        config_file_in(&format!("{}/.config", home))
     } else {
         panic!(
             "Neither XDG_CONFIG_HOME nor HOME environment variables are set. Unable to define \
@@ -19,6 +46,26 @@ pub fn default_config_file_path() -> String {
     }
 }
 
+/// The first existing configuration file in the directory, JSON file when there is none.
+fn config_file_in(dir: &str) -> String {
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| format!("{}/{}", dir, name))
+        .find(|path| std::path::Path::new(path).exists())
+        .unwrap_or_else(|| format!("{}/{}", dir, DEFAULT_CONFIG_FILE_NAME))
+}
+
+/// File where Mutter keeps persistent display configurations.
+pub fn default_monitors_xml_path() -> Option<String> {
+    if let Ok(xdg_config_home) = std::env::var("XDG_CONFIG_HOME") {
+        Some(format!("{}/{}", xdg_config_home, MONITORS_XML_FILE_NAME))
+    } else {
+        std::env::var("HOME")
+            .ok()
+            .map(|home| format!("{}/.config/{}", home, MONITORS_XML_FILE_NAME))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -54,8 +101,41 @@ mod tests {
         default_config_file_path();
     }
 
+    #[test]
+    fn finds_config_file_in_other_format() {
+        let dir = format!("/tmp/{}", uuid::Uuid::new_v4());
+        std::fs::create_dir(&dir).unwrap();
+
+        assert_eq!(
+            format!("{}/display-presets.json", dir),
+            config_file_in(&dir)
+        );
+
+        std::fs::write(format!("{}/display-presets.yaml", dir), "").unwrap();
+        assert_eq!(
+            format!("{}/display-presets.yaml", dir),
+            config_file_in(&dir)
+        );
+    }
+
     #[test]
     fn test_default_timeout() {
         assert_eq!(Duration::from_secs(10), default_timeout())
     }
+
+    #[test]
+    fn test_default_debounce() {
+        assert_eq!(Duration::from_millis(1000), default_debounce())
+    }
+
+    #[test]
+    fn test_default_apply_retries() {
+        assert_eq!(3, default_apply_attempts());
+        assert_eq!(Duration::from_millis(100), default_retry_backoff())
+    }
+
+    #[test]
+    fn test_default_history_depth() {
+        assert_eq!(10, default_history_depth())
+    }
 }
@@ -0,0 +1,154 @@
+use crate::model::{Configuration, Preset};
+
+/// Change of a preset between two versions of configuration.
+#[derive(Debug, PartialEq)]
+pub enum PresetChange<'a> {
+    Added(&'a Preset),
+    Removed(&'a Preset),
+    Changed { name: &'a str, lines: Vec<DiffLine> },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiffLine {
+    Removed(String),
+    Added(String),
+}
+
+/// Compares presets with the same names in both configurations.
+pub fn diff_configurations<'a>(
+    before: &'a Configuration,
+    after: &'a Configuration,
+) -> Vec<PresetChange<'a>> {
+    let mut changes = Vec::new();
+
+    for old in &before.presets {
+        match after.get_preset(&old.name) {
+            Some(new) if new != old => changes.push(PresetChange::Changed {
+                name: &old.name,
+                lines: diff_lines(&to_lines(old), &to_lines(new)),
+            }),
+            Some(_) => {}
+            None => changes.push(PresetChange::Removed(old)),
+        }
+    }
+
+    for new in &after.presets {
+        if before.get_preset(&new.name).is_none() {
+            changes.push(PresetChange::Added(new));
+        }
+    }
+
+    changes
+}
+
+fn to_lines(preset: &Preset) -> Vec<String> {
+    serde_json::to_string_pretty(preset)
+        .unwrap_or_default()
+        .lines()
+        .map(|l| l.to_string())
+        .collect()
+}
+
+/// Lines removed from `before` and added to `after`, based on their longest common
+/// subsequence.
+fn diff_lines(before: &[String], after: &[String]) -> Vec<DiffLine> {
+    // common[i][j] is the length of the longest common subsequence of before[i..] and after[j..]
+    let mut common = vec![vec![0usize; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            common[i][j] = if before[i] == after[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() || j < after.len() {
+        if i < before.len() && j < after.len() && before[i] == after[j] {
+            i += 1;
+            j += 1;
+        } else if j == after.len() || (i < before.len() && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(DiffLine::Removed(before[i].clone()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(after[j].clone()));
+            j += 1;
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{LayoutMode, PresetConfig};
+
+    fn preset(name: &str, physical: bool) -> Preset {
+        Preset {
+            name: name.to_string(),
+            display_config: PresetConfig {
+                layout_mode: Some(if physical {
+                    LayoutMode::Physical
+                } else {
+                    LayoutMode::Logical
+                }),
+                ..Default::default()
+            },
+            rules: None,
+        }
+    }
+
+    #[test]
+    fn finds_changed_presets() {
+        let before = Configuration {
+            presets: vec![
+                preset("desk", false),
+                preset("home", false),
+                preset("work", false),
+            ],
+        };
+        let after = Configuration {
+            presets: vec![
+                preset("desk", true),
+                preset("home", false),
+                preset("tv", false),
+            ],
+        };
+
+        let changes = diff_configurations(&before, &after);
+
+        assert_eq!(
+            vec![
+                PresetChange::Changed {
+                    name: "desk",
+                    lines: vec![
+                        DiffLine::Removed("    \"layout_mode\": \"logical\",".to_string()),
+                        DiffLine::Added("    \"layout_mode\": \"physical\",".to_string()),
+                    ],
+                },
+                PresetChange::Removed(&before.presets[2]),
+                PresetChange::Added(&after.presets[2]),
+            ],
+            changes
+        );
+    }
+
+    #[test]
+    fn diffs_lines() {
+        let lines = |s: &str| s.split(' ').map(|l| l.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                DiffLine::Removed("b".to_string()),
+                DiffLine::Added("x".to_string()),
+                DiffLine::Added("e".to_string()),
+            ],
+            diff_lines(&lines("a b c d"), &lines("a x c d e"))
+        );
+        assert!(diff_lines(&lines("a b"), &lines("a b")).is_empty());
+    }
+}
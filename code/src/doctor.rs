@@ -0,0 +1,250 @@
+use super::backend::DisplayBackend;
+use super::config_file;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Result of a single diagnostic check.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub message: String,
+}
+
+impl Check {
+    fn new(name: &str, status: Status, message: String) -> Check {
+        Check {
+            name: name.to_string(),
+            status,
+            message,
+        }
+    }
+}
+
+/// Properties of org.gnome.Mutter.DisplayConfig used by the application.
+const USED_PROPERTIES: [&str; 2] = ["ApplyMonitorsConfigAllowed", "PanelOrientationManaged"];
+
+/// Runs all checks of the environment. D-Bus checks are skipped when Mutter can't be reached.
+pub fn run_checks(backend: &dyn DisplayBackend, config_path: &str) -> Vec<Check> {
+    let mut checks = vec![check_session_bus(
+        std::env::var("DBUS_SESSION_BUS_ADDRESS").ok(),
+    )];
+
+    let service = check_service(backend);
+    let service_available = service.status == Status::Pass;
+    checks.push(service);
+
+    if service_available {
+        checks.push(check_display_state(backend));
+        checks.extend(USED_PROPERTIES.iter().map(|p| check_property(backend, p)));
+    }
+
+    checks.push(check_session_type(
+        std::env::var("XDG_SESSION_TYPE").ok(),
+        std::env::var("WAYLAND_DISPLAY").ok(),
+    ));
+    checks.push(check_config_file(config_path));
+
+    checks
+}
+
+fn check_session_bus(address: Option<String>) -> Check {
+    match address {
+        Some(address) if !address.is_empty() => Check::new(
+            "Session bus",
+            Status::Pass,
+            format!("DBUS_SESSION_BUS_ADDRESS is {}", address),
+        ),
+        _ => Check::new(
+            "Session bus",
+            Status::Warn,
+            "DBUS_SESSION_BUS_ADDRESS is not set, the session bus may be unreachable. \
+            Run the command from a terminal of the graphical session."
+                .to_string(),
+        ),
+    }
+}
+
+fn check_service(backend: &dyn DisplayBackend) -> Check {
+    match backend.service_owner() {
+        Ok(Some(owner)) => Check::new(
+            "Mutter service",
+            Status::Pass,
+            format!("org.gnome.Mutter.DisplayConfig is owned by {}", owner),
+        ),
+        Ok(None) => Check::new(
+            "Mutter service",
+            Status::Fail,
+            "org.gnome.Mutter.DisplayConfig is not owned by anyone on the session bus. \
+            GNOME Shell is probably not running."
+                .to_string(),
+        ),
+        Err(e) => Check::new("Mutter service", Status::Fail, e.to_string()),
+    }
+}
+
+fn check_display_state(backend: &dyn DisplayBackend) -> Check {
+    match backend.get_current_state() {
+        Ok(state) => Check::new(
+            "Display configuration",
+            Status::Pass,
+            format!(
+                "{} monitor(s) connected, serial {}",
+                state.monitors.len(),
+                state.serial
+            ),
+        ),
+        Err(e) => Check::new("Display configuration", Status::Fail, e.to_string()),
+    }
+}
+
+fn check_property(backend: &dyn DisplayBackend, name: &str) -> Check {
+    match backend.get_property(name) {
+        Ok(value) if name == "ApplyMonitorsConfigAllowed" && value.as_bool() == Some(false) => {
+            Check::new(
+                name,
+                Status::Warn,
+                "Mutter does not allow changing display configuration right now".to_string(),
+            )
+        }
+        Ok(value) => Check::new(name, Status::Pass, format!("{}", value)),
+        Err(e) => Check::new(
+            name,
+            Status::Warn,
+            format!(
+                "Property can't be read, Mutter is probably too old to support it: {}",
+                e
+            ),
+        ),
+    }
+}
+
+fn check_session_type(session_type: Option<String>, wayland_display: Option<String>) -> Check {
+    match (session_type.as_deref(), wayland_display) {
+        (Some("wayland"), _) | (None, Some(_)) => {
+            Check::new("Session type", Status::Pass, "Wayland".to_string())
+        }
+        (Some("x11"), _) => Check::new(
+            "Session type",
+            Status::Warn,
+            "X11. Mutter does not support fractional scaling and logical layout mode on X11"
+                .to_string(),
+        ),
+        (Some(other), _) => Check::new(
+            "Session type",
+            Status::Warn,
+            format!("Unexpected session type '{}'", other),
+        ),
+        (None, None) => Check::new(
+            "Session type",
+            Status::Warn,
+            "Neither XDG_SESSION_TYPE nor WAYLAND_DISPLAY is set".to_string(),
+        ),
+    }
+}
+
+fn check_config_file(config_path: &str) -> Check {
+    if !Path::new(config_path).exists() {
+        return Check::new(
+            "Configuration file",
+            Status::Pass,
+            format!(
+                "{} does not exist yet and will be created on first save",
+                config_path
+            ),
+        );
+    }
+
+    match config_file::load_config(&config_path.to_string()) {
+        Ok(configuration) => Check::new(
+            "Configuration file",
+            Status::Pass,
+            format!(
+                "{} contains {} preset(s)",
+                config_path,
+                configuration.presets.len()
+            ),
+        ),
+        Err(e) => Check::new("Configuration file", Status::Fail, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fake::FakeBackend;
+    use crate::model::{DisplayConfigState, PropValue};
+    use uuid::Uuid;
+
+    fn statuses(checks: &[Check]) -> Vec<(&str, Status)> {
+        checks.iter().map(|c| (c.name.as_str(), c.status)).collect()
+    }
+
+    #[test]
+    fn checks_mutter_service_and_properties() {
+        let backend = FakeBackend::new(DisplayConfigState::default());
+        backend.properties.borrow_mut().insert(
+            "ApplyMonitorsConfigAllowed".to_string(),
+            PropValue::Bool(false),
+        );
+
+        let checks = run_checks(&backend, &format!("/tmp/{}.json", Uuid::new_v4()));
+
+        assert_eq!(
+            vec![
+                ("Mutter service", Status::Pass),
+                ("Display configuration", Status::Pass),
+                ("ApplyMonitorsConfigAllowed", Status::Warn),
+                ("PanelOrientationManaged", Status::Warn),
+            ],
+            statuses(&checks[1..5])
+        );
+        assert_eq!(
+            ("Configuration file", Status::Pass),
+            statuses(&checks)[checks.len() - 1]
+        );
+    }
+
+    #[test]
+    fn skips_dbus_checks_without_mutter() {
+        let mut backend = FakeBackend::new(DisplayConfigState::default());
+        backend.service_owner = None;
+
+        let checks = run_checks(&backend, &format!("/tmp/{}.json", Uuid::new_v4()));
+
+        assert_eq!(("Mutter service", Status::Fail), statuses(&checks)[1]);
+        assert!(!checks.iter().any(|c| c.name == "Display configuration"));
+    }
+
+    #[test]
+    fn detects_session_type() {
+        assert_eq!(
+            Status::Pass,
+            check_session_type(Some("wayland".to_string()), None).status
+        );
+        assert_eq!(
+            Status::Pass,
+            check_session_type(None, Some("wayland-0".to_string())).status
+        );
+        assert_eq!(
+            Status::Warn,
+            check_session_type(Some("x11".to_string()), None).status
+        );
+    }
+
+    #[test]
+    fn fails_on_malformed_config_file() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+        std::fs::write(&path, "{").unwrap();
+
+        assert_eq!(Status::Fail, check_config_file(&path).status);
+    }
+}
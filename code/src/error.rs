@@ -0,0 +1,252 @@
+use super::mutter::{self, Rejection};
+use super::resolve::ResolveError;
+use std::fmt::{Display, Formatter};
+
+/// Error of a command. Each kind of error exits with its own code, see [Error::exit_code].
+#[derive(Debug)]
+pub enum Error {
+    /// Configuration file can't be read or written.
+    ConfigIo {
+        path: String,
+        source: std::io::Error,
+    },
+    /// Configuration file does not contain valid configuration.
+    ConfigParse {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// Configuration file was written by a newer version of the application.
+    ConfigVersion {
+        path: String,
+        version: u32,
+        supported: u32,
+    },
+    PresetNotFound(String),
+    PresetExists(String),
+    /// Preset does not fit connected monitors.
+    Unresolved(ResolveError),
+    /// Session bus or Mutter is not available.
+    DBus(dbus::Error),
+    /// Mutter refused to apply configuration, usually because it was based on a stale serial.
+    AccessDenied(String),
+    /// Mutter refused configuration as invalid or exceeding its limits.
+    Rejected(Rejection),
+    /// Mutter does not accept configuration changes at the moment.
+    ApplyNotAllowed,
+    /// Mutter did not reply in time.
+    Timeout(String),
+    /// monitors.xml file can't be read, written or does not contain valid configurations.
+    MonitorsXml {
+        path: String,
+        message: String,
+    },
+    /// Imported document does not contain a preset which can be read.
+    InvalidPreset {
+        source: String,
+        message: String,
+    },
+    Other(String),
+}
+
+impl Error {
+    /// Exit code of the process for the error. Code 2 is used by argument parser
+    /// for invalid command line.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Other(_) => 1,
+            Error::ConfigIo { .. } => 3,
+            Error::ConfigParse { .. } => 4,
+            Error::PresetNotFound(_) => 5,
+            Error::PresetExists(_) => 6,
+            Error::Unresolved(_) => 7,
+            Error::DBus(_) => 8,
+            Error::AccessDenied(_) => 9,
+            Error::Rejected(Rejection::InvalidArgs(_)) => 10,
+            Error::Rejected(Rejection::LimitsExceeded(_)) => 11,
+            Error::Timeout(_) => 12,
+            Error::ApplyNotAllowed => 13,
+            Error::MonitorsXml { .. } => 14,
+            Error::InvalidPreset { .. } => 15,
+            Error::ConfigVersion { .. } => 16,
+        }
+    }
+
+    pub fn config_parse(path: &str, err: &serde_json::Error) -> Error {
+        Error::config_parse_at(path, err.line(), err.column(), err.to_string())
+    }
+
+    /// Error in the configuration file at the given line and column, both starting at 1.
+    pub fn config_parse_at(path: &str, line: usize, column: usize, message: String) -> Error {
+        // Location is reported separately.
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+
+        Error::ConfigParse {
+            path: path.to_string(),
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ConfigIo { path, source } => write!(
+                f,
+                "Can't access configuration file '{}': {}. Check that the file and its directory are accessible or use --config option.",
+                path, source
+            ),
+            Error::ConfigParse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "Configuration file is malformed at {}:{}:{}: {}. Fix the file or move it away to start with an empty configuration.",
+                path, line, column, message
+            ),
+            Error::ConfigVersion {
+                path,
+                version,
+                supported,
+            } => write!(
+                f,
+                "Configuration file '{}' has version {}, but only version {} and older are supported. Upgrade the application or use --config option to choose another file.",
+                path, version, supported
+            ),
+            Error::PresetNotFound(name) => write!(
+                f,
+                "Preset '{}' was not found. Use 'list' command to see saved presets.",
+                name
+            ),
+            Error::PresetExists(name) => write!(
+                f,
+                "Preset with name '{}' already exists. Use --force option to override.",
+                name
+            ),
+            Error::Unresolved(err) => write!(
+                f,
+                "{}. Use 'validate' command to see which monitors don't match.",
+                err
+            ),
+            Error::DBus(err) => write!(
+                f,
+                "Can't communicate with Mutter over D-Bus: {}. Make sure the command runs inside a GNOME session.",
+                err.message().unwrap_or_default()
+            ),
+            Error::AccessDenied(message) => write!(
+                f,
+                "Mutter denied the configuration: {}. Display configuration has probably changed meanwhile, try again.",
+                message
+            ),
+            Error::Rejected(rejection) => write!(f, "{}", rejection),
+            Error::ApplyNotAllowed => write!(
+                f,
+                "Mutter does not allow changing display configuration right now, e.g. because the screen is locked or the session is remote. Try again from an unlocked local session."
+            ),
+            Error::Timeout(message) => write!(
+                f,
+                "Mutter did not reply in time: {}. Use --timeout option to wait longer.",
+                message
+            ),
+            Error::MonitorsXml { path, message } => write!(
+                f,
+                "Can't use monitors.xml file '{}': {}. Only files in the format written by Mutter 3.26 and later are supported.",
+                path, message
+            ),
+            Error::InvalidPreset { source, message } => write!(
+                f,
+                "Can't import preset from '{}': {}. Make sure it was created with 'export' command.",
+                source, message
+            ),
+            Error::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<dbus::Error> for Error {
+    fn from(err: dbus::Error) -> Self {
+        if let Some(rejection) = Rejection::from_dbus_error(&err) {
+            return Error::Rejected(rejection);
+        }
+
+        let message = err.message().unwrap_or_default().to_string();
+        match err.name() {
+            Some(mutter::ACCESS_DENIED_ERROR) => Error::AccessDenied(message),
+            Some(mutter::NO_REPLY_ERROR) | Some(mutter::TIMEOUT_ERROR) => Error::Timeout(message),
+            _ => Error::DBus(err),
+        }
+    }
+}
+
+impl From<Rejection> for Error {
+    fn from(rejection: Rejection) -> Self {
+        Error::Rejected(rejection)
+    }
+}
+
+impl From<ResolveError> for Error {
+    fn from(err: ResolveError) -> Self {
+        Error::Unresolved(err)
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Other(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Other(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_dbus_errors() {
+        let error = |name: &str| Error::from(dbus::Error::new_custom(name, "message"));
+
+        assert_eq!(9, error(mutter::ACCESS_DENIED_ERROR).exit_code());
+        assert_eq!(10, error(mutter::INVALID_ARGS_ERROR).exit_code());
+        assert_eq!(11, error(mutter::LIMITS_EXCEEDED_ERROR).exit_code());
+        assert_eq!(12, error(mutter::NO_REPLY_ERROR).exit_code());
+        assert_eq!(
+            8,
+            error("org.freedesktop.DBus.Error.ServiceUnknown").exit_code()
+        );
+    }
+
+    #[test]
+    fn reports_parse_error_location() {
+        let err =
+            serde_json::from_str::<serde_json::Value>("{\n  \"presets\": [,]\n}").unwrap_err();
+
+        match Error::config_parse("presets.json", &err) {
+            Error::ConfigParse {
+                path,
+                line,
+                column,
+                message,
+            } => {
+                assert_eq!("presets.json", path);
+                assert_eq!((2, 15), (line, column));
+                assert!(!message.contains("line"));
+            }
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,625 @@
+//! Translation of presets into configuration of other display tools.
+
+use std::collections::HashMap;
+
+use log::debug;
+
+use crate::model::{
+    LayoutMode, LogicalMonitorConfig, MonitorInfo, Preset, PresetConfig, PresetMode,
+};
+use crate::{config_file, resolve};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Preset document of this application, see [config_file::preset_document].
+    Preset,
+    /// Profiles of kanshi, the output configuration daemon for wlroots compositors.
+    Kanshi,
+    /// `output` directives of sway configuration.
+    Sway,
+    /// Shell script calling xrandr, for Xorg sessions.
+    Xrandr,
+    /// Shell script calling gnome-monitor-config, which talks to Mutter directly.
+    GnomeMonitorConfig,
+}
+
+impl Format {
+    /// Whether the format is an executable script.
+    pub fn is_script(&self) -> bool {
+        matches!(self, Format::Xrandr | Format::GnomeMonitorConfig)
+    }
+
+    /// Whether the format can hold only one preset. Directives of several presets in
+    /// sway configuration would override each other.
+    pub fn is_single_preset(&self) -> bool {
+        self.is_script() || matches!(self, Format::Preset | Format::Sway)
+    }
+}
+
+/// List of manufacturers installed by hwdata, which wlroots uses to name monitors.
+const PNP_IDS_PATH: &str = "/usr/share/hwdata/pnp.ids";
+
+/// Manufacturer names by PNP id, e.g. "LG Electronics" for "GSM". Mutter reports only
+/// the id, while wlroots identifies monitors by the name.
+#[derive(Debug, Default)]
+pub struct Manufacturers(HashMap<String, String>);
+
+impl Manufacturers {
+    /// Reads the list installed by hwdata. Empty when hwdata is not installed.
+    pub fn load() -> Manufacturers {
+        match std::fs::read_to_string(PNP_IDS_PATH) {
+            Ok(content) => Manufacturers::parse(&content),
+            Err(e) => {
+                debug!("Can't read {}: {}", PNP_IDS_PATH, e);
+                Manufacturers::default()
+            }
+        }
+    }
+
+    /// Parses lines of PNP id and name separated by a tab.
+    fn parse(content: &str) -> Manufacturers {
+        Manufacturers(
+            content
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .filter_map(|line| line.split_once('\t'))
+                .map(|(id, name)| (id.trim().to_string(), name.trim().to_string()))
+                .collect(),
+        )
+    }
+
+    fn name(&self, pnp_id: &str) -> Option<&str> {
+        self.0.get(pnp_id).map(String::as_str)
+    }
+}
+
+/// Names of transforms as used by wlroots based compositors, indexed by transform
+/// of DisplayConfig D-Bus interface. Both follow wl_output transform.
+const WLR_TRANSFORMS: [&str; 8] = [
+    "normal",
+    "90",
+    "180",
+    "270",
+    "flipped",
+    "flipped-90",
+    "flipped-180",
+    "flipped-270",
+];
+
+/// Rotations of xrandr and monitors.xml, indexed by transform without flipping.
+const ROTATIONS: [(&str, &str); 4] = [
+    ("normal", "normal"),
+    ("left", "left"),
+    ("inverted", "upside_down"),
+    ("right", "right"),
+];
+
+/// Enabled monitor of a preset with its placement.
+#[derive(Debug, PartialEq)]
+struct Output<'a> {
+    monitor_info: &'a MonitorInfo,
+    mode: Option<&'a PresetMode>,
+    underscanning: Option<bool>,
+    x: i32,
+    y: i32,
+    scale: f64,
+    transform: u32,
+    primary: bool,
+}
+
+/// Exports presets in the format. Manufacturers are needed to identify monitors in
+/// kanshi and sway outputs.
+pub fn export(presets: &[&Preset], format: Format, manufacturers: &Manufacturers) -> String {
+    let mut content = String::new();
+
+    for preset in presets {
+        content += &match format {
+            Format::Preset => config_file::preset_document(preset),
+            Format::Kanshi => kanshi_profile(preset, manufacturers),
+            Format::Sway => sway_outputs(preset, manufacturers),
+            Format::Xrandr => xrandr_script(preset),
+            Format::GnomeMonitorConfig => gnome_monitor_config_script(preset),
+        };
+    }
+
+    content
+}
+
+fn kanshi_profile(preset: &Preset, manufacturers: &Manufacturers) -> String {
+    let mut profile = format!("profile {} {{\n", quote(&preset.name));
+    for line in output_lines(&preset.display_config, ",", manufacturers) {
+        profile += &format!("    {}\n", line);
+    }
+    profile += "}\n";
+
+    profile
+}
+
+fn sway_outputs(preset: &Preset, manufacturers: &Manufacturers) -> String {
+    let mut directives = format!("# Preset '{}'\n", single_line(&preset.name));
+    for line in output_lines(&preset.display_config, " ", manufacturers) {
+        directives += &format!("{}\n", line);
+    }
+
+    directives
+}
+
+/// `output` directives, which are the same in kanshi and sway except for the
+/// separator of position coordinates.
+fn output_lines(
+    config: &PresetConfig,
+    position_separator: &str,
+    manufacturers: &Manufacturers,
+) -> Vec<String> {
+    let (outputs, disabled) = outputs(config, LayoutMode::Logical);
+
+    let mut lines = Vec::new();
+    for output in outputs {
+        lines.extend(identity_comment(output.monitor_info, manufacturers));
+        let mut line = format!("output {}", output_name(output.monitor_info, manufacturers));
+        if let Some(mode) = output.mode {
+            line += &format!(
+                " mode {}x{}@{:.3}Hz",
+                mode.width, mode.height, mode.refresh_rate
+            );
+        }
+        line += &format!(
+            " position {}{}{} scale {} transform {}",
+            output.x,
+            position_separator,
+            output.y,
+            output.scale,
+            WLR_TRANSFORMS[output.transform as usize % 8]
+        );
+        lines.push(line);
+    }
+    for monitor_info in disabled {
+        lines.extend(identity_comment(monitor_info, manufacturers));
+        lines.push(format!(
+            "output {} disable",
+            output_name(monitor_info, manufacturers)
+        ));
+    }
+
+    lines
+}
+
+fn xrandr_script(preset: &Preset) -> String {
+    // X11 has no logical layout, positions are always in physical pixels.
+    let (outputs, disabled) = outputs(&preset.display_config, LayoutMode::Physical);
+
+    let mut warnings = vec![
+        "Xorg may name outputs differently than Wayland, e.g. DP1 instead of DP-1.".to_string(),
+    ];
+    let mut arguments = Vec::new();
+
+    for output in &outputs {
+        let connector = &output.monitor_info.connector;
+        let mut argument = format!("--output {}", shell_quote(connector));
+        if let Some(mode) = output.mode {
+            argument += &format!(
+                " --mode {}x{}{} --rate {:.3}",
+                mode.width,
+                mode.height,
+                if mode.interlaced { "i" } else { "" },
+                mode.refresh_rate
+            );
+        }
+        argument += &format!(
+            " --pos {}x{} --rotate {}",
+            output.x,
+            output.y,
+            ROTATIONS[output.transform as usize % 4].0
+        );
+        if output.transform >= 4 {
+            argument += " --reflect x";
+        }
+        if output.primary {
+            argument += " --primary";
+        }
+        arguments.push(argument);
+
+        if output.scale != 1.0 {
+            warnings.push(format!(
+                "Scale {} of {} can't be set per output on Xorg. Set the scaling factor in GNOME Settings.",
+                output.scale,
+                shell_quote(connector)
+            ));
+        }
+        if output.underscanning == Some(true) {
+            warnings.push(format!(
+                "Underscanning of {} is not set. Some drivers support it with: xrandr --output {} --set underscan on",
+                shell_quote(connector),
+                shell_quote(connector)
+            ));
+        }
+    }
+    for monitor_info in disabled {
+        arguments.push(format!(
+            "--output {} --off",
+            shell_quote(&monitor_info.connector)
+        ));
+    }
+
+    script(preset, &warnings, "xrandr", &arguments)
+}
+
+fn gnome_monitor_config_script(preset: &Preset) -> String {
+    let config = &preset.display_config;
+    let mut warnings = Vec::new();
+    if config.layout_mode() == LayoutMode::Physical {
+        warnings.push(
+            "Positions are in physical layout mode and are interpreted in the layout mode of the session."
+                .to_string(),
+        );
+    }
+
+    let mut arguments = Vec::new();
+    for lm in &config.logical_monitors {
+        let mut argument = format!(
+            "-L{} -x {} -y {} -s {}",
+            if lm.primary { "p" } else { "" },
+            lm.x,
+            lm.y,
+            lm.scale
+        );
+        if lm.transform % 4 != 0 {
+            argument += &format!(" -t {}", ROTATIONS[lm.transform as usize % 4].1);
+        }
+        if lm.transform >= 4 {
+            warnings.push(format!(
+                "Flipped transform {} can't be set with gnome-monitor-config.",
+                lm.transform
+            ));
+        }
+
+        for monitor in &lm.monitors {
+            let connector = &monitor.monitor_info.connector;
+            argument += &format!(" -M {}", shell_quote(connector));
+            match &monitor.mode {
+                Some(mode) => argument += &format!(" -m {}", shell_quote(&mode.id)),
+                None => warnings.push(format!(
+                    "No mode is saved for {}, preferred mode is used.",
+                    shell_quote(connector)
+                )),
+            }
+            if monitor.is_underscanning() == Some(true) {
+                warnings.push(format!(
+                    "Underscanning of {} can't be set with gnome-monitor-config.",
+                    shell_quote(connector)
+                ));
+            }
+        }
+        arguments.push(argument);
+    }
+
+    script(preset, &warnings, "gnome-monitor-config set", &arguments)
+}
+
+/// Shell script running the command with arguments, one per line. Values in the
+/// arguments must be quoted with [shell_quote].
+fn script(preset: &Preset, warnings: &[String], command: &str, arguments: &[String]) -> String {
+    let mut script = format!(
+        "#!/bin/sh\n# Preset '{}', exported by {}\n",
+        single_line(&preset.name),
+        env!("CARGO_PKG_NAME")
+    );
+    for warning in warnings {
+        script += &format!("# WARNING: {}\n", single_line(warning));
+    }
+
+    script += command;
+    for argument in arguments {
+        script += &format!(" \\\n    {}", argument);
+    }
+    script += "\n";
+
+    script
+}
+
+/// Monitors of logical monitors, positioned in given layout mode, and disabled monitors.
+fn outputs(config: &PresetConfig, layout_mode: LayoutMode) -> (Vec<Output<'_>>, Vec<&MonitorInfo>) {
+    let mut outputs = Vec::new();
+    for lm in &config.logical_monitors {
+        for monitor in &lm.monitors {
+            outputs.push(Output {
+                monitor_info: &monitor.monitor_info,
+                mode: monitor.mode.as_ref(),
+                underscanning: monitor.is_underscanning(),
+                x: lm.x,
+                y: lm.y,
+                scale: lm.scale,
+                transform: lm.transform,
+                primary: lm.primary,
+            });
+        }
+    }
+
+    if config.layout_mode() != layout_mode {
+        translate_layout(&mut outputs, config.layout_mode(), layout_mode);
+    }
+
+    (outputs, config.disabled_monitors.iter().collect())
+}
+
+fn translate_layout(outputs: &mut [Output], from: LayoutMode, to: LayoutMode) {
+    let mut logical_monitors: Vec<LogicalMonitorConfig> = outputs
+        .iter()
+        .map(|o| LogicalMonitorConfig {
+            x: o.x,
+            y: o.y,
+            scale: o.scale,
+            transform: o.transform,
+            primary: false,
+            monitors: vec![],
+        })
+        .collect();
+    let mode_sizes: Vec<(i32, i32)> = outputs
+        .iter()
+        .map(|o| match o.mode {
+            Some(mode) if o.transform % 2 == 1 => (mode.height, mode.width),
+            Some(mode) => (mode.width, mode.height),
+            None => (0, 0),
+        })
+        .collect();
+
+    resolve::translate_layout(&mut logical_monitors, &mode_sizes, from, to);
+
+    for (output, lm) in outputs.iter_mut().zip(logical_monitors) {
+        output.x = lm.x;
+        output.y = lm.y;
+    }
+}
+
+/// Monitor is identified by "make model serial" description as wlroots names it, so
+/// that the configuration follows it across connectors. Connector name is used when
+/// the manufacturer name of the PNP id is unknown or the monitor has no identity,
+/// quoted unless it's a plain name.
+fn output_name(monitor_info: &MonitorInfo, manufacturers: &Manufacturers) -> String {
+    if let Some(description) = description(monitor_info, manufacturers) {
+        return quote(&description);
+    }
+
+    let connector = &monitor_info.connector;
+    if connector
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        connector.clone()
+    } else {
+        quote(connector)
+    }
+}
+
+fn description(monitor_info: &MonitorInfo, manufacturers: &Manufacturers) -> Option<String> {
+    let MonitorInfo {
+        vendor,
+        product,
+        serial,
+        ..
+    } = monitor_info;
+    if [vendor, product, serial]
+        .iter()
+        .any(|v| v.is_empty() || v.as_str() == "unknown")
+    {
+        return None;
+    }
+
+    manufacturers
+        .name(vendor)
+        .map(|make| format!("{} {} {}", make, product, serial))
+}
+
+/// Comment with identity of a monitor identified by its connector, so that the
+/// description can be written by hand.
+fn identity_comment(monitor_info: &MonitorInfo, manufacturers: &Manufacturers) -> Option<String> {
+    if !monitor_info.has_identity() || description(monitor_info, manufacturers).is_some() {
+        return None;
+    }
+
+    Some(format!(
+        "# {}: vendor {}, product {}, serial {}",
+        single_line(&monitor_info.connector),
+        single_line(&monitor_info.vendor),
+        single_line(&monitor_info.product),
+        single_line(&monitor_info.serial)
+    ))
+}
+
+fn quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        single_line(value)
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    )
+}
+
+/// Quotes the value as a single word of POSIX shell, with no characters interpreted.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Text for a comment line or a quoted string, with line breaks replaced by spaces so
+/// that the text can't continue on a line of its own.
+fn single_line(text: &str) -> String {
+    text.replace(['\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{info, mode, with_current_mode};
+    use crate::model::{DisplayConfigState, LogicalMonitor, Monitor, Props};
+
+    /// Monitor with identity unless it's the built-in one.
+    fn monitor(connector: &str, width: i32, height: i32) -> Monitor {
+        let serial = if connector == "eDP-1" { "" } else { "0x01" };
+        let mut mode = mode(
+            &format!("{}x{}@60.000", width, height),
+            width,
+            height,
+            59.951,
+        );
+        mode.supported_scales.push(2.0);
+
+        with_current_mode(info(connector, serial), mode)
+    }
+
+    fn manufacturers() -> Manufacturers {
+        Manufacturers::parse("GSM\tLG Electronics\nSAM\tSamsung Electric Company\n")
+    }
+
+    fn logical_monitor(monitor: &Monitor, x: i32, scale: f64, transform: u32) -> LogicalMonitor {
+        LogicalMonitor {
+            x,
+            y: 0,
+            scale,
+            transform,
+            primary: x == 0,
+            monitors: vec![monitor.monitor_info.clone()],
+            properties: Props::new(),
+        }
+    }
+
+    fn preset() -> Preset {
+        let builtin = monitor("eDP-1", 2560, 1600);
+        let external = monitor("DP-1", 3840, 2160);
+        let tv = monitor("HDMI-1", 1920, 1080);
+
+        Preset {
+            name: "desk".to_string(),
+            display_config: PresetConfig::from_state(&DisplayConfigState {
+                serial: 1,
+                logical_monitors: vec![
+                    logical_monitor(&builtin, 0, 2.0, 0),
+                    logical_monitor(&external, 1280, 1.0, 1),
+                ],
+                monitors: vec![builtin, external, tv],
+                properties: Props::new(),
+            }),
+            rules: None,
+        }
+    }
+
+    #[test]
+    fn exports_kanshi_profile() {
+        assert_eq!(
+            "profile \"desk\" {\n\
+            \x20   output eDP-1 mode 2560x1600@59.951Hz position 0,0 scale 2 transform normal\n\
+            \x20   output \"LG Electronics LG Ultra HD 0x01\" mode 3840x2160@59.951Hz position 1280,0 scale 1 transform 90\n\
+            \x20   output \"LG Electronics LG Ultra HD 0x01\" disable\n\
+            }\n",
+            export(&[&preset()], Format::Kanshi, &manufacturers())
+        );
+    }
+
+    #[test]
+    fn exports_sway_outputs() {
+        assert_eq!(
+            "# Preset 'desk'\n\
+            output eDP-1 mode 2560x1600@59.951Hz position 0 0 scale 2 transform normal\n\
+            output \"LG Electronics LG Ultra HD 0x01\" mode 3840x2160@59.951Hz position 1280 0 scale 1 transform 90\n\
+            output \"LG Electronics LG Ultra HD 0x01\" disable\n",
+            export(&[&preset()], Format::Sway, &manufacturers())
+        );
+    }
+
+    #[test]
+    fn exports_several_presets_only_as_kanshi_profiles() {
+        assert!(!Format::Kanshi.is_single_preset());
+        assert!(Format::Sway.is_single_preset());
+    }
+
+    #[test]
+    fn falls_back_to_connector_for_unknown_manufacturer() {
+        assert_eq!(
+            "# Preset 'desk'\n\
+            output eDP-1 mode 2560x1600@59.951Hz position 0 0 scale 2 transform normal\n\
+            # DP-1: vendor GSM, product LG Ultra HD, serial 0x01\n\
+            output DP-1 mode 3840x2160@59.951Hz position 1280 0 scale 1 transform 90\n\
+            # HDMI-1: vendor GSM, product LG Ultra HD, serial 0x01\n\
+            output HDMI-1 disable\n",
+            export(&[&preset()], Format::Sway, &Manufacturers::default())
+        );
+    }
+
+    #[test]
+    fn exports_xrandr_script() {
+        let mut preset = preset();
+        preset.display_config.logical_monitors[1].transform = 5;
+
+        let script = export(&[&preset], Format::Xrandr, &Manufacturers::default());
+
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains(
+            "# WARNING: Scale 2 of 'eDP-1' can't be set per output on Xorg. Set the scaling factor in GNOME Settings.\n"
+        ));
+        assert!(script.ends_with(
+            "xrandr \\\n\
+            \x20   --output 'eDP-1' --mode 2560x1600 --rate 59.951 --pos 0x0 --rotate normal --primary \\\n\
+            \x20   --output 'DP-1' --mode 3840x2160 --rate 59.951 --pos 2560x0 --rotate left --reflect x \\\n\
+            \x20   --output 'HDMI-1' --off\n"
+        ));
+    }
+
+    #[test]
+    fn exports_gnome_monitor_config_script() {
+        let script = export(
+            &[&preset()],
+            Format::GnomeMonitorConfig,
+            &Manufacturers::default(),
+        );
+
+        assert!(!script.contains("WARNING"));
+        assert!(script.ends_with(
+            "gnome-monitor-config set \\\n\
+            \x20   -Lp -x 0 -y 0 -s 2 -M 'eDP-1' -m '2560x1600@60.000' \\\n\
+            \x20   -L -x 1280 -y 0 -s 1 -t left -M 'DP-1' -m '3840x2160@60.000'\n"
+        ));
+    }
+
+    #[test]
+    fn quotes_untrusted_values_in_scripts() {
+        let mut preset = preset();
+        preset.name = "x\nrm -rf ~".to_string();
+        let monitor = &mut preset.display_config.logical_monitors[1].monitors[0];
+        monitor.monitor_info.connector = "DP-1;id".to_string();
+        monitor.mode.as_mut().unwrap().id = "$(id)'`id`".to_string();
+
+        for format in [Format::Xrandr, Format::GnomeMonitorConfig, Format::Sway] {
+            let content = export(&[&preset], format, &Manufacturers::default());
+
+            assert!(
+                !content.lines().any(|l| l.starts_with("rm ")),
+                "{}",
+                content
+            );
+            assert!(content.contains("# Preset 'x rm -rf ~'"), "{}", content);
+        }
+        let xrandr = export(&[&preset], Format::Xrandr, &Manufacturers::default());
+        assert!(xrandr.contains(" --output 'DP-1;id' --mode"));
+        preset.display_config.logical_monitors[0].monitors[0]
+            .monitor_info
+            .connector = "eDP-1;exec id".to_string();
+        assert!(export(&[&preset], Format::Sway, &Manufacturers::default())
+            .contains("output \"eDP-1;exec id\" mode"));
+        let gnome_monitor_config = export(
+            &[&preset],
+            Format::GnomeMonitorConfig,
+            &Manufacturers::default(),
+        );
+        assert!(gnome_monitor_config.contains(" -M 'DP-1;id' -m '$(id)'\\''`id`'\n"));
+    }
+
+    #[test]
+    fn translates_physical_layout() {
+        let mut preset = preset();
+        preset.display_config.logical_monitors[1].x = 2560;
+        preset.display_config.layout_mode = Some(LayoutMode::Physical);
+
+        let (outputs, _) = outputs(&preset.display_config, LayoutMode::Logical);
+
+        assert_eq!(1280, outputs[1].x);
+    }
+}
@@ -0,0 +1,137 @@
+//! Display configuration states shared by tests.
+
+use crate::model::{
+    Condition, Configuration, DisplayConfigState, LayoutMode, LogicalMonitor, Mode, Monitor,
+    MonitorInfo, Preset, PresetConfig, PropValue, Props, SelectionRules,
+};
+
+/// Identity of an LG monitor. Empty serial gives a monitor without identity, like
+/// a built-in panel.
+pub fn info(connector: &str, serial: &str) -> MonitorInfo {
+    let identity = |value: &str| if serial.is_empty() { "" } else { value }.to_string();
+
+    MonitorInfo {
+        connector: connector.to_string(),
+        vendor: identity("GSM"),
+        product: identity("LG Ultra HD"),
+        serial: serial.to_string(),
+    }
+}
+
+/// Mode supporting scale 1 only.
+pub fn mode(id: &str, width: i32, height: i32, refresh_rate: f64) -> Mode {
+    Mode {
+        id: id.to_string(),
+        width,
+        height,
+        refresh_rate,
+        preferred_scale: 1.0,
+        supported_scales: vec![1.0],
+        properties: Props::new(),
+    }
+}
+
+/// Monitor with the mode as its only and current mode.
+pub fn with_current_mode(info: MonitorInfo, mut mode: Mode) -> Monitor {
+    mode.properties
+        .insert("is-current".to_string(), PropValue::Bool(true));
+
+    Monitor {
+        monitor_info: info,
+        modes: vec![mode],
+        properties: Props::new(),
+    }
+}
+
+/// Monitor running 1920x1080 at 60 Hz.
+pub fn monitor(connector: &str, serial: &str) -> Monitor {
+    with_current_mode(
+        info(connector, serial),
+        mode("1920x1080@60", 1920, 1080, 60.0),
+    )
+}
+
+/// State with every monitor in its own logical monitor, side by side from left to
+/// right. The first one is primary.
+pub fn state(monitors: Vec<Monitor>) -> DisplayConfigState {
+    DisplayConfigState {
+        serial: 1,
+        logical_monitors: monitors
+            .iter()
+            .enumerate()
+            .map(|(i, m)| LogicalMonitor {
+                x: 1920 * i as i32,
+                y: 0,
+                scale: 1.0,
+                transform: 0,
+                primary: i == 0,
+                monitors: vec![m.monitor_info.clone()],
+                properties: Props::new(),
+            })
+            .collect(),
+        monitors,
+        properties: Props::new(),
+    }
+}
+
+/// Configuration with every kind of value stored in presets: fractional refresh rate,
+/// monitor without current mode, properties of nested types, disabled monitor and
+/// rules with conditions.
+pub fn configuration() -> Configuration {
+    let laptop = with_current_mode(
+        info("eDP-1", ""),
+        mode("2560x1600@165.004", 2560, 1600, 165.00399780273438),
+    );
+    let mut tv = monitor("HDMI-1", "2");
+    tv.modes[0].properties.clear();
+
+    let mut desk = PresetConfig::from_state(&state(vec![laptop, monitor("DP-1", "1"), tv]));
+    desk.layout_mode = Some(LayoutMode::Physical);
+    desk.logical_monitors[1].monitors[0].properties = Props::from([
+        ("is-underscanning".to_string(), PropValue::Bool(false)),
+        ("max-bpc".to_string(), PropValue::UInt64(1 << 40)),
+        (
+            "color-mode".to_string(),
+            PropValue::Struct(vec![
+                PropValue::UInt32(1),
+                PropValue::String("bt2100".to_string()),
+            ]),
+        ),
+        (
+            "backlight".to_string(),
+            PropValue::Dict(Props::from([
+                ("value".to_string(), PropValue::Int32(-1)),
+                ("scale".to_string(), PropValue::Double(0.75)),
+            ])),
+        ),
+    ]);
+    desk.disabled_monitors.push(info("DP-2", "3"));
+
+    Configuration {
+        presets: vec![
+            Preset {
+                name: "desk".to_string(),
+                display_config: desk,
+                rules: Some(SelectionRules {
+                    priority: 2,
+                    conditions: vec![
+                        Condition::Builtin { present: true },
+                        Condition::Vendor {
+                            vendor: "GSM".to_string(),
+                        },
+                        Condition::MonitorCount {
+                            min: Some(2),
+                            max: None,
+                        },
+                        Condition::SameMonitors,
+                    ],
+                }),
+            },
+            Preset {
+                name: "laptop".to_string(),
+                display_config: PresetConfig::from_state(&state(vec![monitor("eDP-1", "")])),
+                rules: None,
+            },
+        ],
+    }
+}
@@ -0,0 +1,323 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config_file;
+use crate::error::Error;
+use crate::migrations::{self, CONFIG_VERSION};
+use crate::model::Configuration;
+
+/// Command recorded for versions of the configuration file which were not written
+/// by the application, e.g. edited by hand.
+pub const EXTERNAL_CHANGE: &str = "(changed outside of the application)";
+
+/// Version of the configuration file kept in the history.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Command line which produced the version.
+    pub command: String,
+    /// Seconds since Unix epoch.
+    pub timestamp: u64,
+    pub configuration: Configuration,
+}
+
+/// History entry as written to its file, with version of the configuration, so that
+/// entries written by older versions of the application are upgraded when read.
+#[derive(Serialize)]
+struct StoredEntry<'a> {
+    version: u32,
+    #[serde(flatten)]
+    entry: &'a HistoryEntry,
+}
+
+/// Directory with versions of the configuration file, next to the file itself.
+pub fn history_dir(config_path: &str) -> String {
+    format!("{}.history", config_path)
+}
+
+/// Reads versions of the configuration file, the newest first. The newest version is
+/// the current content of the file unless the file was changed outside of the application.
+pub fn read_history(config_path: &str) -> Result<Vec<HistoryEntry>, Error> {
+    let dir = history_dir(config_path);
+
+    let mut entries = entry_files(&dir)?
+        .into_iter()
+        .map(|(_, path)| read_entry(&path))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.reverse();
+
+    Ok(entries)
+}
+
+/// Writes configuration to the file and records it in the history together with the
+/// command that produced it. Only `depth` previous versions are kept. When current
+/// content of the file is not in the history yet, it is recorded first, so that it
+/// can be restored as well.
+pub fn write_config(
+    config_path: &String,
+    conf: &Configuration,
+    command: &str,
+    depth: usize,
+) -> Result<(), Error> {
+    if depth == 0 {
+        return config_file::write_config(config_path, conf);
+    }
+
+    let dir = history_dir(config_path);
+    std::fs::create_dir_all(&dir).map_err(|source| Error::ConfigIo {
+        path: dir.clone(),
+        source,
+    })?;
+
+    let files = entry_files(&dir)?;
+    let mut next = files.last().map(|(seq, _)| seq + 1).unwrap_or_default();
+
+    if let Some(current) = unrecorded_content(config_path, &files)? {
+        debug!("Recording current content of {} in history", config_path);
+        write_entry(&dir, next, &current)?;
+        next += 1;
+    }
+
+    config_file::write_config(config_path, conf)?;
+
+    let entry = HistoryEntry {
+        command: command.to_string(),
+        timestamp: now(),
+        configuration: conf.clone(),
+    };
+    write_entry(&dir, next, &entry)?;
+
+    prune(&dir, depth + 1)
+}
+
+/// Current content of the configuration file when it differs from the newest version
+/// in the history.
+fn unrecorded_content(
+    config_path: &String,
+    files: &[(u64, String)],
+) -> Result<Option<HistoryEntry>, Error> {
+    if !Path::new(config_path).exists() {
+        return Ok(None);
+    }
+
+    let configuration = match config_file::read_config(config_path) {
+        Ok(configuration) => configuration,
+        Err(e) => {
+            warn!("Current configuration is not kept in history: {}", e);
+            return Ok(None);
+        }
+    };
+
+    if let Some((_, latest)) = files.last() {
+        if read_entry(latest)?.configuration == configuration {
+            return Ok(None);
+        }
+    }
+
+    let timestamp = std::fs::metadata(config_path)
+        .and_then(|m| m.modified())
+        .map(timestamp)
+        .unwrap_or_else(|_| now());
+
+    Ok(Some(HistoryEntry {
+        command: EXTERNAL_CHANGE.to_string(),
+        timestamp,
+        configuration,
+    }))
+}
+
+/// Files of history entries sorted from the oldest to the newest.
+fn entry_files(dir: &str) -> Result<Vec<(u64, String)>, Error> {
+    let io_error = |source| Error::ConfigIo {
+        path: dir.to_string(),
+        source,
+    };
+
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(io_error(e)),
+    };
+
+    let mut files = Vec::new();
+    for dir_entry in read_dir {
+        let path = dir_entry.map_err(io_error)?.path();
+        let seq = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix(".json"))
+            .and_then(|n| n.parse::<u64>().ok());
+
+        if let Some(seq) = seq {
+            files.push((seq, path.to_string_lossy().to_string()));
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+fn read_entry(path: &str) -> Result<HistoryEntry, Error> {
+    let content = std::fs::read_to_string(path).map_err(|source| Error::ConfigIo {
+        path: path.to_string(),
+        source,
+    })?;
+
+    let mut value: Value =
+        serde_json::from_str(&content).map_err(|e| Error::config_parse(path, &e))?;
+
+    // Entries written before the version was recorded don't have it.
+    let version = value["version"].as_u64().unwrap_or_default() as u32;
+    if version > CONFIG_VERSION {
+        Err(Error::ConfigVersion {
+            path: path.to_string(),
+            version,
+            supported: CONFIG_VERSION,
+        })?
+    }
+    migrations::migrate(&mut value["configuration"], version).map_err(|e| {
+        format!(
+            "Can't upgrade history entry '{}' from version {}: {}",
+            path, version, e
+        )
+    })?;
+
+    serde_json::from_value(value).map_err(|e| Error::config_parse(path, &e))
+}
+
+fn write_entry(dir: &str, seq: u64, entry: &HistoryEntry) -> Result<(), Error> {
+    let path = format!("{}/{}.json", dir, seq);
+    let content = serde_json::to_string(&StoredEntry {
+        version: CONFIG_VERSION,
+        entry,
+    })
+    .map_err(|e| e.to_string())?;
+
+    config_file::replace_file(Path::new(&path), content.as_bytes())
+        .map_err(|source| Error::ConfigIo { path, source })
+}
+
+/// Removes the oldest entries so that at most `keep` entries remain.
+fn prune(dir: &str, keep: usize) -> Result<(), Error> {
+    let files = entry_files(dir)?;
+    let excess = files.len().saturating_sub(keep);
+
+    for (_, path) in files.into_iter().take(excess) {
+        debug!("Removing old history entry {}", path);
+        std::fs::remove_file(&path).map_err(|source| Error::ConfigIo { path, source })?;
+    }
+
+    Ok(())
+}
+
+fn now() -> u64 {
+    timestamp(SystemTime::now())
+}
+
+fn timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Preset, PresetConfig};
+    use uuid::Uuid;
+
+    fn configuration(names: &[&str]) -> Configuration {
+        Configuration {
+            presets: names
+                .iter()
+                .map(|name| Preset {
+                    name: name.to_string(),
+                    display_config: PresetConfig::default(),
+                    rules: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn commands(path: &str) -> Vec<String> {
+        read_history(path)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.command)
+            .collect()
+    }
+
+    #[test]
+    fn records_versions_with_commands() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+
+        write_config(&path, &configuration(&["a"]), "save a", 10).unwrap();
+        write_config(&path, &configuration(&["a", "b"]), "save b", 10).unwrap();
+
+        let history = read_history(&path).unwrap();
+        assert_eq!(vec!["save b", "save a"], commands(&path));
+        assert_eq!(configuration(&["a", "b"]), history[0].configuration);
+    }
+
+    #[test]
+    fn keeps_only_configured_depth() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+
+        for name in ["a", "b", "c", "d"] {
+            write_config(&path, &configuration(&[name]), &format!("save {}", name), 2).unwrap();
+        }
+
+        assert_eq!(vec!["save d", "save c", "save b"], commands(&path));
+    }
+
+    #[test]
+    fn records_external_changes() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+
+        write_config(&path, &configuration(&["a"]), "save a", 10).unwrap();
+        config_file::write_config(&path, &configuration(&["edited"])).unwrap();
+        write_config(&path, &configuration(&["b"]), "save b", 10).unwrap();
+
+        let history = read_history(&path).unwrap();
+        assert_eq!(vec!["save b", EXTERNAL_CHANGE, "save a"], commands(&path));
+        assert_eq!(configuration(&["edited"]), history[1].configuration);
+    }
+
+    #[test]
+    fn upgrades_entries_of_older_versions() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+        std::fs::create_dir(history_dir(&path)).unwrap();
+        std::fs::write(
+            format!("{}/0.json", history_dir(&path)),
+            r#"{"command": "save old", "timestamp": 1, "configuration": {"presets": [
+                {"name": "old", "display_config":
+                    {"serial": 1, "monitors": [], "logical_monitors": [], "properties": {}}}
+            ]}}"#,
+        )
+        .unwrap();
+        write_config(&path, &configuration(&["a"]), "save a", 10).unwrap();
+
+        let history = read_history(&path).unwrap();
+
+        assert_eq!(vec!["save a", "save old"], commands(&path));
+        assert_eq!(configuration(&["old"]), history[1].configuration);
+        assert!(
+            std::fs::read_to_string(format!("{}/1.json", history_dir(&path)))
+                .unwrap()
+                .starts_with(&format!("{{\"version\":{},", CONFIG_VERSION))
+        );
+    }
+
+    #[test]
+    fn does_not_record_history_with_zero_depth() {
+        let path = format!("/tmp/{}.json", Uuid::new_v4());
+
+        write_config(&path, &configuration(&["a"]), "save a", 0).unwrap();
+
+        assert!(read_history(&path).unwrap().is_empty());
+        assert!(!Path::new(&history_dir(&path)).exists());
+    }
+}
@@ -0,0 +1,26 @@
+//! Presets of GNOME display configuration, applied through Mutter's
+//! org.gnome.Mutter.DisplayConfig D-Bus interface.
+
+pub mod backend;
+pub mod cli;
+mod config_file;
+mod config_format;
+mod defaults;
+mod diff;
+mod doctor;
+pub mod error;
+mod export;
+#[cfg(test)]
+mod fixtures;
+mod history;
+mod migrations;
+pub mod model;
+mod monitors_xml;
+pub mod mutter;
+#[allow(clippy::all, dead_code)]
+mod mutter_dbus;
+mod out;
+mod prompt;
+pub mod resolve;
+mod selection;
+mod validation;
@@ -1,23 +1,16 @@
-#![allow(dead_code)]
-
 use log::{debug, error, LevelFilter};
+use mutter_display_presets::backend::MutterBackend;
+use mutter_display_presets::cli;
 use std::process::exit;
 
-mod cli;
-mod config_file;
-mod defaults;
-mod model;
-mod mutter;
-mod mutter_dbus;
-mod out;
-
 fn main() {
     if let Ok(cli) = cli::Cli::parse() {
         simple_logger::SimpleLogger::new()
             .with_level(if cli.options.verbose {
                 LevelFilter::Debug
             } else {
-                LevelFilter::Error
+                // Warnings tell about changes made to what the user asked for.
+                LevelFilter::Warn
             })
             .without_timestamps()
             .init()
@@ -25,9 +18,11 @@ fn main() {
 
         debug!("Running with {:?}", &cli.options);
 
-        if let Err(e) = cli.command.execute(&cli.options) {
+        let backend = MutterBackend::new(cli.options.timeout);
+
+        if let Err(e) = cli.command.execute(&cli.options, &backend) {
             error!("{}", e);
-            exit(1);
+            exit(e.exit_code());
         }
     }
 }
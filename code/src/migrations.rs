@@ -0,0 +1,135 @@
+use serde_json::Value;
+
+use crate::model::{DisplayConfigState, PresetConfig};
+
+/// Upgrades configuration from the version equal to its index to the next version.
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// Migrations of the configuration file, the oldest first.
+const MIGRATIONS: &[Migration] = &[add_version, desired_configuration];
+
+/// Version of the configuration file written by this application.
+pub const CONFIG_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Upgrades configuration of the given version to [CONFIG_VERSION].
+pub fn migrate(configuration: &mut Value, version: u32) -> Result<(), String> {
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(configuration)?;
+    }
+    Ok(())
+}
+
+/// Version 0 was written without the version field, its content is the same as of version 1.
+fn add_version(_configuration: &mut Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// Version 2 stores desired configuration in presets instead of the whole state reported
+/// by Mutter.
+fn desired_configuration(configuration: &mut Value) -> Result<(), String> {
+    let presets = configuration
+        .get_mut("presets")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+
+    for preset in presets {
+        desired_preset_configuration(preset)?;
+    }
+    Ok(())
+}
+
+/// Replaces the whole state reported by Mutter, stored in a preset before version 2,
+/// with the desired configuration of the preset.
+pub fn desired_preset_configuration(preset: &mut Value) -> Result<(), String> {
+    let name = preset["name"].as_str().unwrap_or_default().to_string();
+    if let Some(display_config) = preset.get_mut("display_config") {
+        let state: DisplayConfigState = serde_json::from_value(display_config.take())
+            .map_err(|e| format!("Preset '{}': {}", name, e))?;
+        *display_config =
+            serde_json::to_value(PresetConfig::from_state(&state)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrates_unversioned_configuration() {
+        let mut configuration = json!({"presets": []});
+
+        migrate(&mut configuration, 0).unwrap();
+
+        assert_eq!(json!({"presets": []}), configuration);
+    }
+
+    #[test]
+    fn converts_state_to_desired_configuration() {
+        let monitor_info = json!({
+            "connector": "DP-1",
+            "vendor": "GSM",
+            "product": "LG Ultra HD",
+            "serial": "1"
+        });
+        let mode = |id: &str, current: bool| {
+            json!({
+                "id": id,
+                "width": 1920,
+                "height": 1080,
+                "refresh_rate": 60.0,
+                "preferred_scale": 1.0,
+                "supported_scales": [1.0],
+                "properties": {"is-current": {"bool": current}}
+            })
+        };
+        let mut configuration = json!({"presets": [{
+            "name": "desk",
+            "display_config": {
+                "serial": 7,
+                "monitors": [{
+                    "monitor_info": monitor_info,
+                    "modes": [mode("1920x1080@50.000", false), mode("1920x1080@60.000", true)],
+                    "properties": {}
+                }],
+                "logical_monitors": [{
+                    "x": 0,
+                    "y": 0,
+                    "scale": 1.0,
+                    "transform": 0,
+                    "primary": true,
+                    "monitors": [monitor_info],
+                    "properties": {}
+                }],
+                "properties": {"layout-mode": {"u_int32": 2}}
+            }
+        }]});
+
+        migrate(&mut configuration, 1).unwrap();
+
+        assert_eq!(
+            json!({
+                "layout_mode": "physical",
+                "logical_monitors": [{
+                    "x": 0,
+                    "y": 0,
+                    "scale": 1.0,
+                    "transform": 0,
+                    "primary": true,
+                    "monitors": [{
+                        "monitor_info": monitor_info,
+                        "mode": {
+                            "id": "1920x1080@60.000",
+                            "width": 1920,
+                            "height": 1080,
+                            "refresh_rate": 60.0
+                        }
+                    }]
+                }]
+            }),
+            configuration["presets"][0]["display_config"]
+        );
+    }
+}
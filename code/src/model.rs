@@ -1,9 +1,119 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-pub type Props = HashMap<String, String>;
+pub type Props = HashMap<String, PropValue>;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// Value of a D-Bus property, keeping the original D-Bus type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PropValue {
+    Bool(bool),
+    Byte(u8),
+    Int16(i16),
+    #[serde(rename = "uint16", alias = "u_int16")]
+    UInt16(u16),
+    Int32(i32),
+    #[serde(rename = "uint32", alias = "u_int32")]
+    UInt32(u32),
+    Int64(i64),
+    #[serde(rename = "uint64", alias = "u_int64")]
+    UInt64(u64),
+    Double(f64),
+    String(String),
+    Struct(Vec<PropValue>),
+    Array(Vec<PropValue>),
+    /// Dictionary with string keys, `a{sv}` in D-Bus.
+    Dict(Props),
+    /// Value saved by earlier versions, which stored every property as a string.
+    #[serde(untagged)]
+    Legacy(String),
+}
+
+impl PropValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            PropValue::Bool(v) => Some(*v),
+            PropValue::Legacy(v) => Some(v == "1"),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            PropValue::Byte(v) => Some(*v as i64),
+            PropValue::Int16(v) => Some(*v as i64),
+            PropValue::UInt16(v) => Some(*v as i64),
+            PropValue::Int32(v) => Some(*v as i64),
+            PropValue::UInt32(v) => Some(*v as i64),
+            PropValue::Int64(v) => Some(*v),
+            PropValue::UInt64(v) => i64::try_from(*v).ok(),
+            PropValue::Legacy(v) => v.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        self.as_i64().and_then(|v| u32::try_from(v).ok())
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        self.as_i64().and_then(|v| i32::try_from(v).ok())
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            PropValue::Double(v) => Some(*v),
+            PropValue::Legacy(v) => v.parse().ok(),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropValue::String(v) | PropValue::Legacy(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_fields(&self) -> Option<&[PropValue]> {
+        match self {
+            PropValue::Struct(fields) => Some(fields),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PropValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |values: &[PropValue]| -> String {
+            values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        match self {
+            PropValue::Bool(v) => write!(f, "{}", v),
+            PropValue::Double(v) => write!(f, "{}", v),
+            PropValue::String(v) | PropValue::Legacy(v) => write!(f, "{}", v),
+            PropValue::Struct(fields) => write!(f, "({})", join(fields)),
+            PropValue::Array(items) => write!(f, "[{}]", join(items)),
+            PropValue::Dict(entries) => {
+                let mut keys: Vec<&String> = entries.keys().collect();
+                keys.sort();
+                let entries: Vec<String> = keys
+                    .into_iter()
+                    .map(|k| format!("{}: {}", k, entries[k]))
+                    .collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            other => write!(f, "{}", other.as_i64().unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Configuration {
     pub presets: Vec<Preset>,
 }
@@ -14,13 +124,70 @@ impl Configuration {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Preset {
     pub name: String,
-    pub display_config: DisplayConfigState,
+    pub display_config: PresetConfig,
+    /// Rules for automatic selection. When not set, preset is selected when the same
+    /// monitors are connected as it was saved with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rules: Option<SelectionRules>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SelectionRules {
+    /// Preset with higher priority wins when multiple presets satisfy their conditions.
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "condition", rename_all = "kebab-case")]
+pub enum Condition {
+    /// Built-in panel, e.g. laptop screen, is (or is not) connected.
+    Builtin { present: bool },
+    /// A monitor of the vendor is connected.
+    Vendor { vendor: String },
+    /// A monitor of the product is connected.
+    Product { product: String },
+    /// Number of connected monitors is within the range.
+    MonitorCount {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<usize>,
+    },
+    /// The same monitors are connected as the preset was saved with.
+    SameMonitors,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Condition::Builtin { present: true } => write!(f, "built-in panel is connected"),
+            Condition::Builtin { present: false } => {
+                write!(f, "built-in panel is not connected")
+            }
+            Condition::Vendor { vendor } => {
+                write!(f, "monitor of vendor '{}' is connected", vendor)
+            }
+            Condition::Product { product } => {
+                write!(f, "monitor '{}' is connected", product)
+            }
+            Condition::MonitorCount { min, max } => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "{} to {} monitors are connected", min, max),
+                (Some(min), None) => write!(f, "{} or more monitors are connected", min),
+                (None, Some(max)) => write!(f, "{} or less monitors are connected", max),
+                (None, None) => write!(f, "any number of monitors is connected"),
+            },
+            Condition::SameMonitors => write!(f, "same monitors are connected as saved"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct DisplayConfigState {
     pub serial: u32,
     pub monitors: Vec<Monitor>,
@@ -28,7 +195,106 @@ pub struct DisplayConfigState {
     pub properties: Props,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+impl DisplayConfigState {
+    /// Layout mode of the configuration. Logical layout mode is assumed when
+    /// Mutter does not report it.
+    pub fn layout_mode(&self) -> LayoutMode {
+        match self
+            .properties
+            .get("layout-mode")
+            .and_then(PropValue::as_u32)
+        {
+            Some(2) => LayoutMode::Physical,
+            _ => LayoutMode::Logical,
+        }
+    }
+
+    pub fn supports_changing_layout_mode(&self) -> bool {
+        bool_prop(&self.properties, "supports-changing-layout-mode")
+    }
+
+    pub fn global_scale_required(&self) -> bool {
+        bool_prop(&self.properties, "global-scale-required")
+    }
+
+    /// Updates the state the way Mutter does after applying `config`.
+    pub fn apply(&mut self, config: &MonitorsConfig) {
+        self.serial += 1;
+
+        for monitor in &mut self.monitors {
+            let monitor_config = config
+                .logical_monitors
+                .iter()
+                .flat_map(|lm| lm.monitors.iter())
+                .find(|m| m.connector == monitor.monitor_info.connector);
+
+            for mode in &mut monitor.modes {
+                mode.properties.insert(
+                    "is-current".to_string(),
+                    PropValue::Bool(monitor_config.is_some_and(|m| m.mode_id == mode.id)),
+                );
+            }
+
+            let underscanning = monitor_config
+                .and_then(|m| m.properties.get("enable_underscanning"))
+                .and_then(PropValue::as_bool);
+            if let (Some(enable), Some(_)) = (underscanning, monitor.is_underscanning()) {
+                monitor
+                    .properties
+                    .insert("is-underscanning".to_string(), PropValue::Bool(enable));
+            }
+        }
+
+        self.logical_monitors = config
+            .logical_monitors
+            .iter()
+            .map(|lm| LogicalMonitor {
+                x: lm.x,
+                y: lm.y,
+                scale: lm.scale,
+                transform: lm.transform,
+                primary: lm.primary,
+                monitors: lm
+                    .monitors
+                    .iter()
+                    .filter_map(|m| {
+                        self.monitors
+                            .iter()
+                            .find(|sm| sm.monitor_info.connector == m.connector)
+                            .map(|sm| sm.monitor_info.clone())
+                    })
+                    .collect(),
+                properties: Props::new(),
+            })
+            .collect();
+
+        if let Some(layout_mode) = config.properties.get("layout-mode") {
+            self.properties
+                .insert("layout-mode".to_string(), layout_mode.clone());
+        }
+    }
+}
+
+/// The way logical monitors are laid out on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutMode {
+    /// Logical monitor dimension is the monitor mode dimension divided by the scale.
+    Logical,
+    /// Logical monitor dimension is the monitor mode dimension.
+    Physical,
+}
+
+impl LayoutMode {
+    pub fn as_u32(&self) -> u32 {
+        match self {
+            LayoutMode::Logical => 1,
+            LayoutMode::Physical => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MonitorInfo {
     pub connector: String,
     pub vendor: String,
@@ -36,7 +302,32 @@ pub struct MonitorInfo {
     pub serial: String,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+impl MonitorInfo {
+    /// Whether monitor reports vendor, product or serial which can be used to
+    /// recognize it regardless of the connector it is plugged into.
+    pub fn has_identity(&self) -> bool {
+        [&self.vendor, &self.product, &self.serial]
+            .iter()
+            .any(|v| !v.is_empty() && v.as_str() != "unknown")
+    }
+
+    /// Whether both monitor infos describe the same physical display.
+    pub fn same_hardware(&self, other: &MonitorInfo) -> bool {
+        self.has_identity()
+            && self.vendor == other.vendor
+            && self.product == other.product
+            && self.serial == other.serial
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {} (serial '{}', last seen on {})",
+            self.vendor, self.product, self.serial, self.connector
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Mode {
     pub id: String,
     pub width: i32,
@@ -47,7 +338,21 @@ pub struct Mode {
     pub properties: Props,
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+impl Mode {
+    pub fn is_current(&self) -> bool {
+        bool_prop(&self.properties, "is-current")
+    }
+
+    pub fn is_preferred(&self) -> bool {
+        bool_prop(&self.properties, "is-preferred")
+    }
+
+    pub fn is_interlaced(&self) -> bool {
+        bool_prop(&self.properties, "is-interlaced")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Monitor {
     pub monitor_info: MonitorInfo,
     pub modes: Vec<Mode>,
@@ -55,15 +360,41 @@ pub struct Monitor {
 }
 
 impl Monitor {
+    pub fn get_current_mode(&self) -> Option<&Mode> {
+        self.modes.iter().find(|m| m.is_current())
+    }
+
     pub fn get_current_mode_id(&self) -> Option<&String> {
-        self.modes
-            .iter()
-            .find(|m| m.properties.get("is-current").is_some_and(|v| v == "1"))
-            .map(|cur_md| &cur_md.id)
+        self.get_current_mode().map(|cur_md| &cur_md.id)
+    }
+
+    /// Whether underscanning is enabled. `None` when the monitor does not support underscanning.
+    pub fn is_underscanning(&self) -> Option<bool> {
+        self.properties
+            .get("is-underscanning")
+            .and_then(PropValue::as_bool)
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        bool_prop(&self.properties, "is-builtin")
+    }
+
+    pub fn display_name(&self) -> Option<&str> {
+        self.properties
+            .get("display-name")
+            .and_then(PropValue::as_str)
+    }
+
+    /// Maximum size of the screen as (width, height). `None` means unlimited size.
+    pub fn max_screen_size(&self) -> Option<(i32, i32)> {
+        match self.properties.get("max-screen-size")?.as_fields()? {
+            [width, height] => Some((width.as_i32()?, height.as_i32()?)),
+            _ => None,
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LogicalMonitor {
     pub x: i32,
     pub y: i32,
@@ -74,31 +405,252 @@ pub struct LogicalMonitor {
     pub properties: Props,
 }
 
+/// Display configuration stored in a preset: placement of logical monitors and the mode
+/// of every monitor, without the rest of the state reported by Mutter.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PresetConfig {
+    /// Not set when Mutter did not report layout mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout_mode: Option<LayoutMode>,
+    pub logical_monitors: Vec<PresetLogicalMonitor>,
+    /// Monitors which were connected, but turned off.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled_monitors: Vec<MonitorInfo>,
+}
+
+impl PresetConfig {
+    /// Current configuration of the state. Modes other than current ones, serial and
+    /// properties of the state are dropped.
+    pub fn from_state(state: &DisplayConfigState) -> PresetConfig {
+        let saved = |info: &MonitorInfo| {
+            state
+                .monitors
+                .iter()
+                .find(|m| m.monitor_info.connector == info.connector)
+        };
+
+        let logical_monitors: Vec<PresetLogicalMonitor> = state
+            .logical_monitors
+            .iter()
+            .map(|lm| PresetLogicalMonitor {
+                x: lm.x,
+                y: lm.y,
+                scale: lm.scale,
+                transform: lm.transform,
+                primary: lm.primary,
+                monitors: lm
+                    .monitors
+                    .iter()
+                    .map(|info| PresetMonitor {
+                        monitor_info: info.clone(),
+                        mode: saved(info)
+                            .and_then(|m| m.get_current_mode())
+                            .map(PresetMode::from_mode),
+                        properties: saved(info)
+                            .map(|m| configurable_properties(&m.properties))
+                            .unwrap_or_default(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let disabled_monitors = state
+            .monitors
+            .iter()
+            .map(|m| &m.monitor_info)
+            .filter(|info| {
+                !logical_monitors
+                    .iter()
+                    .flat_map(|lm| &lm.monitors)
+                    .any(|m| m.monitor_info.connector == info.connector)
+            })
+            .cloned()
+            .collect();
+
+        PresetConfig {
+            layout_mode: state
+                .properties
+                .contains_key("layout-mode")
+                .then(|| state.layout_mode()),
+            logical_monitors,
+            disabled_monitors,
+        }
+    }
+
+    /// Layout mode of the configuration. Logical layout mode is assumed when it is not set.
+    pub fn layout_mode(&self) -> LayoutMode {
+        self.layout_mode.unwrap_or(LayoutMode::Logical)
+    }
+
+    /// Monitors of all logical monitors.
+    pub fn monitors(&self) -> impl Iterator<Item = &PresetMonitor> {
+        self.logical_monitors.iter().flat_map(|lm| &lm.monitors)
+    }
+
+    /// Whether both configurations lay out the same monitors the same way, using the same
+    /// modes. Mode ids and properties of monitors are not compared.
+    pub fn same_layout(&self, other: &PresetConfig) -> bool {
+        self.layout_mode() == other.layout_mode() && self.layout() == other.layout()
+    }
+
+    /// Placement and mode of every monitor of logical monitors, sorted by connector.
+    fn layout(&self) -> Vec<(&MonitorInfo, String, i32, i32, String, u32, bool)> {
+        let mut layout: Vec<_> = self
+            .logical_monitors
+            .iter()
+            .flat_map(|lm| lm.monitors.iter().map(move |m| (lm, m)))
+            .map(|(lm, m)| {
+                (
+                    &m.monitor_info,
+                    m.mode
+                        .as_ref()
+                        .map(|m| format!("{}x{}@{:.3}", m.width, m.height, m.refresh_rate))
+                        .unwrap_or_default(),
+                    lm.x,
+                    lm.y,
+                    format!("{:.3}", lm.scale),
+                    lm.transform,
+                    lm.primary,
+                )
+            })
+            .collect();
+        layout.sort_by(|a, b| a.0.connector.cmp(&b.0.connector));
+        layout
+    }
+}
+
+/// Monitor properties which can be set when applying configuration. Other properties
+/// describe the hardware or the session and are not kept in presets.
+const CONFIGURABLE_MONITOR_PROPERTIES: [&str; 1] = ["is-underscanning"];
+
+fn configurable_properties(properties: &Props) -> Props {
+    properties
+        .iter()
+        .filter(|(name, _)| CONFIGURABLE_MONITOR_PROPERTIES.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresetLogicalMonitor {
+    pub x: i32,
+    pub y: i32,
+    pub scale: f64,
+    pub transform: u32,
+    pub primary: bool,
+    pub monitors: Vec<PresetMonitor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresetMonitor {
+    pub monitor_info: MonitorInfo,
+    /// Not set when Mutter did not report current mode, preferred mode is used then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<PresetMode>,
+    /// Configurable properties of the monitor, e.g. "is-underscanning".
+    #[serde(default, skip_serializing_if = "Props::is_empty")]
+    pub properties: Props,
+}
+
+impl PresetMonitor {
+    /// Whether underscanning is enabled. `None` when the monitor does not support underscanning.
+    pub fn is_underscanning(&self) -> Option<bool> {
+        self.properties
+            .get("is-underscanning")
+            .and_then(PropValue::as_bool)
+    }
+}
+
+/// Mode of a monitor. Id is used when the monitor still supports it, otherwise the closest
+/// mode by resolution and refresh rate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresetMode {
+    pub id: String,
+    pub width: i32,
+    pub height: i32,
+    pub refresh_rate: f64,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interlaced: bool,
+}
+
+impl PresetMode {
+    pub fn from_mode(mode: &Mode) -> PresetMode {
+        PresetMode {
+            id: mode.id.clone(),
+            width: mode.width,
+            height: mode.height,
+            refresh_rate: mode.refresh_rate,
+            interlaced: mode.is_interlaced(),
+        }
+    }
+}
+
+fn bool_prop(props: &Props, name: &str) -> bool {
+    props
+        .get(name)
+        .and_then(PropValue::as_bool)
+        .unwrap_or(false)
+}
+
+/// Display configuration resolved against currently connected monitors,
+/// ready to be passed to Mutter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorsConfig {
+    pub logical_monitors: Vec<LogicalMonitorConfig>,
+    /// Global properties passed to ApplyMonitorsConfig, e.g. "layout-mode".
+    pub properties: Props,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalMonitorConfig {
+    pub x: i32,
+    pub y: i32,
+    pub scale: f64,
+    pub transform: u32,
+    pub primary: bool,
+    pub monitors: Vec<MonitorConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorConfig {
+    pub connector: String,
+    pub mode_id: String,
+    /// Monitor properties passed to ApplyMonitorsConfig, e.g. "enable_underscanning".
+    pub properties: Props,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn generate_preset_with_name(name: &str, serial: u32) -> Preset {
+    fn generate_preset_with_name(name: &str, x: i32) -> Preset {
         Preset {
             name: String::from(name),
-            display_config: DisplayConfigState {
-                serial,
-                monitors: vec![],
-                logical_monitors: vec![],
-                properties: Props::new(),
+            display_config: PresetConfig {
+                layout_mode: None,
+                logical_monitors: vec![PresetLogicalMonitor {
+                    x,
+                    y: 0,
+                    scale: 1.0,
+                    transform: 0,
+                    primary: true,
+                    monitors: vec![],
+                }],
+                disabled_monitors: vec![],
             },
+            rules: None,
         }
     }
 
     #[test]
     fn get_preset_by_name() {
-        let preset1 = generate_preset_with_name("Preset1", 1);
-        let preset2 = generate_preset_with_name("Preset2", 2);
+        let preset1 = generate_preset_with_name("Preset1", 0);
+        let preset2 = generate_preset_with_name("Preset2", 1920);
 
         let conf = Configuration {
             presets: vec![
-                generate_preset_with_name("Preset1", 1),
-                generate_preset_with_name("Preset2", 2),
+                generate_preset_with_name("Preset1", 0),
+                generate_preset_with_name("Preset2", 1920),
             ],
         };
 
@@ -107,6 +659,47 @@ mod tests {
         assert!(conf.get_preset("Missing").is_none());
     }
 
+    #[test]
+    fn keeps_only_configurable_monitor_properties() {
+        let info = MonitorInfo {
+            connector: "HDMI-1".to_string(),
+            vendor: "SAM".to_string(),
+            product: "TV".to_string(),
+            serial: "1".to_string(),
+        };
+        let state = DisplayConfigState {
+            monitors: vec![Monitor {
+                monitor_info: info.clone(),
+                modes: vec![],
+                properties: [
+                    ("is-underscanning".to_string(), PropValue::Bool(true)),
+                    ("is-builtin".to_string(), PropValue::Bool(false)),
+                    (
+                        "display-name".to_string(),
+                        PropValue::String("TV".to_string()),
+                    ),
+                ]
+                .into(),
+            }],
+            logical_monitors: vec![LogicalMonitor {
+                x: 0,
+                y: 0,
+                scale: 1.0,
+                transform: 0,
+                primary: true,
+                monitors: vec![info],
+                properties: HashMap::new(),
+            }],
+            ..Default::default()
+        };
+
+        let config = PresetConfig::from_state(&state);
+
+        let monitor = config.monitors().next().unwrap();
+        assert_eq!(1, monitor.properties.len());
+        assert_eq!(Some(true), monitor.is_underscanning());
+    }
+
     #[test]
     fn get_current_mode_id() {
         let monitor = Monitor {
@@ -124,7 +717,7 @@ mod tests {
                     refresh_rate: 0.0,
                     preferred_scale: 0.0,
                     supported_scales: vec![],
-                    properties: [("is-current".to_string(), "1".to_string())].into(),
+                    properties: [("is-current".to_string(), PropValue::Bool(true))].into(),
                 },
                 Mode {
                     id: "2".to_string(),
@@ -141,4 +734,141 @@ mod tests {
 
         assert_eq!("1", monitor.get_current_mode_id().unwrap());
     }
+
+    #[test]
+    fn monitor_info_same_hardware() {
+        let info = |connector: &str, serial: &str| MonitorInfo {
+            connector: connector.to_string(),
+            vendor: "GSM".to_string(),
+            product: "LG Ultra HD".to_string(),
+            serial: serial.to_string(),
+        };
+        let unknown = |connector: &str| MonitorInfo {
+            connector: connector.to_string(),
+            vendor: "unknown".to_string(),
+            product: "unknown".to_string(),
+            serial: "".to_string(),
+        };
+
+        assert!(info("DP-3", "123").same_hardware(&info("DP-5", "123")));
+        assert!(!info("DP-3", "123").same_hardware(&info("DP-3", "456")));
+        assert!(!unknown("DP-3").same_hardware(&unknown("DP-5")));
+    }
+
+    #[test]
+    fn prop_value_json_round_trip() {
+        let props: Props = [
+            ("is-builtin".to_string(), PropValue::Bool(true)),
+            ("layout-mode".to_string(), PropValue::UInt32(2)),
+            (
+                "max-screen-size".to_string(),
+                PropValue::Struct(vec![PropValue::Int32(16384), PropValue::Int32(16384)]),
+            ),
+            (
+                "details".to_string(),
+                PropValue::Dict([("edid".to_string(), PropValue::Array(vec![]))].into()),
+            ),
+        ]
+        .into();
+
+        let json = serde_json::to_string(&props).unwrap();
+
+        assert_eq!(props, serde_json::from_str::<Props>(&json).unwrap());
+    }
+
+    #[test]
+    fn writes_unsigned_props_with_plain_names() {
+        let props: Props = [("layout-mode".to_string(), PropValue::UInt32(2))].into();
+
+        assert_eq!(
+            r#"{"layout-mode":{"uint32":2}}"#,
+            serde_json::to_string(&props).unwrap()
+        );
+        assert_eq!(
+            props,
+            serde_json::from_str::<Props>(r#"{"layout-mode":{"u_int32":2}}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn reads_legacy_string_props() {
+        let props: Props =
+            serde_json::from_str(r#"{"is-current": "1", "layout-mode": "2"}"#).unwrap();
+
+        assert_eq!(Some(true), props["is-current"].as_bool());
+        assert_eq!(Some(2), props["layout-mode"].as_u32());
+    }
+
+    #[test]
+    fn max_screen_size() {
+        let monitor = Monitor {
+            monitor_info: MonitorInfo {
+                connector: "".to_string(),
+                vendor: "".to_string(),
+                product: "".to_string(),
+                serial: "".to_string(),
+            },
+            modes: vec![],
+            properties: [(
+                "max-screen-size".to_string(),
+                PropValue::Struct(vec![PropValue::Int32(8192), PropValue::Int32(4096)]),
+            )]
+            .into(),
+        };
+
+        assert_eq!(Some((8192, 4096)), monitor.max_screen_size());
+    }
+
+    #[test]
+    fn applies_config_to_state() {
+        let mode = |id: &str, current: bool| Mode {
+            id: id.to_string(),
+            width: 1920,
+            height: 1080,
+            refresh_rate: 60.0,
+            preferred_scale: 1.0,
+            supported_scales: vec![1.0],
+            properties: [("is-current".to_string(), PropValue::Bool(current))].into(),
+        };
+        let info = MonitorInfo {
+            connector: "HDMI-1".to_string(),
+            vendor: "GSM".to_string(),
+            product: "LG".to_string(),
+            serial: "1".to_string(),
+        };
+        let mut state = DisplayConfigState {
+            serial: 5,
+            monitors: vec![Monitor {
+                monitor_info: info.clone(),
+                modes: vec![mode("a", true), mode("b", false)],
+                properties: [("is-underscanning".to_string(), PropValue::Bool(false))].into(),
+            }],
+            logical_monitors: vec![],
+            properties: Props::new(),
+        };
+
+        state.apply(&MonitorsConfig {
+            logical_monitors: vec![LogicalMonitorConfig {
+                x: 0,
+                y: 0,
+                scale: 1.0,
+                transform: 1,
+                primary: true,
+                monitors: vec![MonitorConfig {
+                    connector: "HDMI-1".to_string(),
+                    mode_id: "b".to_string(),
+                    properties: [("enable_underscanning".to_string(), PropValue::Bool(true))]
+                        .into(),
+                }],
+            }],
+            properties: [("layout-mode".to_string(), PropValue::UInt32(2))].into(),
+        });
+
+        assert_eq!(6, state.serial);
+        assert_eq!("b", state.monitors[0].get_current_mode_id().unwrap());
+        assert_eq!(Some(true), state.monitors[0].is_underscanning());
+        assert_eq!(1, state.logical_monitors[0].transform);
+        assert_eq!(vec![info], state.logical_monitors[0].monitors);
+        assert_eq!(LayoutMode::Physical, state.layout_mode());
+    }
 }
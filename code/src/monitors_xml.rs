@@ -0,0 +1,595 @@
+//! Mutter's own store of persistent display configurations, `~/.config/monitors.xml`.
+
+use std::str::FromStr;
+
+use xmltree::{Element, EmitterConfig, XMLNode};
+
+use crate::model::{
+    LayoutMode, MonitorInfo, PresetConfig, PresetLogicalMonitor, PresetMode, PresetMonitor,
+    PropValue, Props,
+};
+
+/// Version of monitors.xml format written by Mutter since 3.26.
+pub const SUPPORTED_VERSION: &str = "2";
+
+/// Parses configurations stored in monitors.xml.
+pub fn parse(content: &str) -> Result<Vec<PresetConfig>, String> {
+    let root = Element::parse(content.as_bytes()).map_err(|e| e.to_string())?;
+
+    if root.name != "monitors" {
+        return Err(format!("Unexpected root element <{}>", root.name));
+    }
+    match root.attributes.get("version").map(String::as_str) {
+        Some(SUPPORTED_VERSION) => {}
+        Some(version) => Err(format!(
+            "Unsupported version '{}', only version {} is supported",
+            version, SUPPORTED_VERSION
+        ))?,
+        None => Err("Version of the file is not specified")?,
+    }
+
+    children(&root, "configuration")
+        .enumerate()
+        .map(|(i, configuration)| {
+            parse_configuration(configuration)
+                .map_err(|e| format!("Configuration {}: {}", i + 1, e))
+        })
+        .collect()
+}
+
+fn parse_configuration(element: &Element) -> Result<PresetConfig, String> {
+    let mut config = PresetConfig {
+        layout_mode: match child_text(element, "layoutmode").as_deref() {
+            Some("logical") => Some(LayoutMode::Logical),
+            Some("physical") => Some(LayoutMode::Physical),
+            Some(other) => Err(format!("Invalid layout mode '{}'", other))?,
+            None => None,
+        },
+        ..Default::default()
+    };
+
+    for logical_monitor in children(element, "logicalmonitor") {
+        config
+            .logical_monitors
+            .push(parse_logical_monitor(logical_monitor)?);
+    }
+
+    if config.logical_monitors.is_empty() {
+        Err("No logical monitors")?
+    }
+
+    if let Some(disabled) = element.get_child("disabled") {
+        for spec in children(disabled, "monitorspec") {
+            config.disabled_monitors.push(parse_monitor_spec(spec)?);
+        }
+    }
+
+    Ok(config)
+}
+
+fn parse_logical_monitor(element: &Element) -> Result<PresetLogicalMonitor, String> {
+    let mut logical_monitor = PresetLogicalMonitor {
+        x: required(element, "x")?,
+        y: required(element, "y")?,
+        scale: match child_text(element, "scale") {
+            Some(_) => required(element, "scale")?,
+            None => 1.0,
+        },
+        transform: match element.get_child("transform") {
+            Some(transform) => parse_transform(transform)?,
+            None => 0,
+        },
+        primary: match child_text(element, "primary") {
+            Some(primary) => parse_bool(&primary)?,
+            None => false,
+        },
+        monitors: vec![],
+    };
+
+    for monitor_element in children(element, "monitor") {
+        let spec = monitor_element
+            .get_child("monitorspec")
+            .ok_or("Monitor without <monitorspec>")?;
+        let monitor_info = parse_monitor_spec(spec)?;
+
+        let mode = monitor_element
+            .get_child("mode")
+            .ok_or("Monitor without <mode>")?;
+
+        let mut properties = Props::new();
+        if let Some(underscanning) = child_text(monitor_element, "underscanning") {
+            properties.insert(
+                "is-underscanning".to_string(),
+                PropValue::Bool(parse_bool(&underscanning)?),
+            );
+        }
+
+        logical_monitor.monitors.push(PresetMonitor {
+            monitor_info,
+            mode: Some(parse_mode(mode)?),
+            properties,
+        });
+    }
+
+    if logical_monitor.monitors.is_empty() {
+        Err("Logical monitor without monitors")?
+    }
+
+    Ok(logical_monitor)
+}
+
+fn parse_monitor_spec(element: &Element) -> Result<MonitorInfo, String> {
+    Ok(MonitorInfo {
+        connector: required(element, "connector")?,
+        vendor: required(element, "vendor")?,
+        product: required(element, "product")?,
+        serial: required(element, "serial")?,
+    })
+}
+
+/// Mode stored in monitors.xml, with id formatted the way Mutter formats mode ids.
+fn parse_mode(element: &Element) -> Result<PresetMode, String> {
+    let width: i32 = required(element, "width")?;
+    let height: i32 = required(element, "height")?;
+    let refresh_rate: f64 = required(element, "rate")?;
+    let interlaced = children(element, "flag").any(|f| text(f).as_deref() == Some("interlace"));
+
+    Ok(PresetMode {
+        id: format!(
+            "{}x{}{}@{:.3}",
+            width,
+            height,
+            if interlaced { "i" } else { "" },
+            refresh_rate
+        ),
+        width,
+        height,
+        refresh_rate,
+        interlaced,
+    })
+}
+
+/// Transform as used by DisplayConfig D-Bus interface: rotation counterclockwise
+/// in steps of 90 degrees, plus 4 when flipped.
+fn parse_transform(element: &Element) -> Result<u32, String> {
+    let rotation = match child_text(element, "rotation").as_deref() {
+        Some("normal") | None => 0,
+        Some("left") => 1,
+        Some("upside_down") => 2,
+        Some("right") => 3,
+        Some(other) => Err(format!("Invalid rotation '{}'", other))?,
+    };
+    let flipped = match child_text(element, "flipped") {
+        Some(flipped) => parse_bool(&flipped)?,
+        None => false,
+    };
+
+    Ok(rotation + if flipped { 4 } else { 0 })
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "yes" | "true" => Ok(true),
+        "no" | "false" => Ok(false),
+        other => Err(format!("Invalid boolean '{}'", other)),
+    }
+}
+
+/// Configuration element for the display configuration.
+pub fn to_configuration(config: &PresetConfig) -> Result<Element, String> {
+    let mut configuration = Element::new("configuration");
+
+    if let Some(layout_mode) = config.layout_mode {
+        let layout_mode = match layout_mode {
+            LayoutMode::Logical => "logical",
+            LayoutMode::Physical => "physical",
+        };
+        push(&mut configuration, text_element("layoutmode", layout_mode));
+    }
+
+    for lm in &config.logical_monitors {
+        let mut logical_monitor = Element::new("logicalmonitor");
+        push(&mut logical_monitor, text_element("x", lm.x));
+        push(&mut logical_monitor, text_element("y", lm.y));
+        push(&mut logical_monitor, text_element("scale", lm.scale));
+        if lm.primary {
+            push(&mut logical_monitor, text_element("primary", "yes"));
+        }
+        if lm.transform != 0 {
+            push(&mut logical_monitor, transform_element(lm.transform)?);
+        }
+
+        for saved in &lm.monitors {
+            let info = &saved.monitor_info;
+            let mode = saved
+                .mode
+                .as_ref()
+                .ok_or_else(|| format!("No current mode for monitor {}", info.connector))?;
+
+            let mut monitor = Element::new("monitor");
+            push(&mut monitor, monitor_spec_element(info));
+            push(&mut monitor, mode_element(mode));
+            if saved.is_underscanning() == Some(true) {
+                push(&mut monitor, text_element("underscanning", "yes"));
+            }
+            push(&mut logical_monitor, monitor);
+        }
+
+        push(&mut configuration, logical_monitor);
+    }
+
+    if !config.disabled_monitors.is_empty() {
+        let mut element = Element::new("disabled");
+        for info in &config.disabled_monitors {
+            push(&mut element, monitor_spec_element(info));
+        }
+        push(&mut configuration, element);
+    }
+
+    Ok(configuration)
+}
+
+/// Adds configurations to the content of an existing monitors.xml, or to an empty one.
+/// Existing configuration for the same set of monitors is replaced, as Mutter keeps one
+/// configuration per set of monitors. Other content is kept as is. Returns the new content
+/// and, for every added configuration, whether it replaced an existing one.
+pub fn merge(
+    existing: Option<&str>,
+    configurations: Vec<Element>,
+) -> Result<(String, Vec<bool>), String> {
+    // Nodes before and after the root element, e.g. comments.
+    let (before, mut root, after) = match existing {
+        Some(content) => {
+            // Refuse to touch files which can't be read back.
+            parse(content)?;
+            let mut nodes = Element::parse_all(content.as_bytes()).map_err(|e| e.to_string())?;
+            let position = nodes
+                .iter()
+                .position(|node| node.as_element().is_some())
+                .ok_or("No root element")?;
+            let after = nodes.split_off(position + 1);
+            let Some(XMLNode::Element(root)) = nodes.pop() else {
+                unreachable!()
+            };
+            (nodes, root, after)
+        }
+        None => {
+            let mut root = Element::new("monitors");
+            root.attributes
+                .insert("version".to_string(), SUPPORTED_VERSION.to_string());
+            (Vec::new(), root, Vec::new())
+        }
+    };
+
+    let mut replaced = Vec::new();
+    for configuration in configurations {
+        let key = monitor_specs(&configuration);
+        let position = root.children.iter().position(|node| {
+            node.as_element()
+                .is_some_and(|e| e.name == "configuration" && monitor_specs(e) == key)
+        });
+
+        match position {
+            Some(position) => root.children[position] = XMLNode::Element(configuration),
+            None => push(&mut root, configuration),
+        }
+        replaced.push(position.is_some());
+    }
+
+    let mut content = Vec::new();
+    root.write_with_config(
+        &mut content,
+        EmitterConfig::new()
+            .perform_indent(true)
+            .write_document_declaration(false),
+    )
+    .map_err(|e| e.to_string())?;
+    let content = comments(&before)
+        + &String::from_utf8(content).map_err(|e| e.to_string())?
+        + "\n"
+        + &comments(&after);
+
+    parse(&content).map_err(|e| format!("Exported content is not valid: {}", e))?;
+
+    Ok((content, replaced))
+}
+
+/// Comments among the nodes, one per line. xmltree writes only the root element.
+fn comments(nodes: &[XMLNode]) -> String {
+    nodes
+        .iter()
+        .filter_map(XMLNode::as_comment)
+        .map(|comment| format!("<!--{}-->\n", comment))
+        .collect()
+}
+
+/// Sorted monitor specs of all monitors of the configuration, including disabled ones.
+fn monitor_specs(element: &Element) -> Vec<Vec<Option<String>>> {
+    let mut specs = Vec::new();
+    collect_monitor_specs(element, &mut specs);
+    specs.sort();
+    specs
+}
+
+fn collect_monitor_specs(element: &Element, specs: &mut Vec<Vec<Option<String>>>) {
+    for child in element.children.iter().filter_map(|node| node.as_element()) {
+        if child.name == "monitorspec" {
+            specs.push(
+                ["connector", "vendor", "product", "serial"]
+                    .iter()
+                    .map(|name| child_text(child, name))
+                    .collect(),
+            );
+        } else {
+            collect_monitor_specs(child, specs);
+        }
+    }
+}
+
+fn transform_element(transform: u32) -> Result<Element, String> {
+    let rotation = match transform % 4 {
+        0 => "normal",
+        1 => "left",
+        2 => "upside_down",
+        _ => "right",
+    };
+    if transform > 7 {
+        Err(format!("Invalid transform {}", transform))?
+    }
+
+    let mut element = Element::new("transform");
+    push(&mut element, text_element("rotation", rotation));
+    push(
+        &mut element,
+        text_element("flipped", if transform >= 4 { "yes" } else { "no" }),
+    );
+    Ok(element)
+}
+
+fn monitor_spec_element(info: &MonitorInfo) -> Element {
+    let mut element = Element::new("monitorspec");
+    push(&mut element, text_element("connector", &info.connector));
+    push(&mut element, text_element("vendor", &info.vendor));
+    push(&mut element, text_element("product", &info.product));
+    push(&mut element, text_element("serial", &info.serial));
+    element
+}
+
+fn mode_element(mode: &PresetMode) -> Element {
+    let mut element = Element::new("mode");
+    push(&mut element, text_element("width", mode.width));
+    push(&mut element, text_element("height", mode.height));
+    push(&mut element, text_element("rate", mode.refresh_rate));
+    if mode.interlaced {
+        push(&mut element, text_element("flag", "interlace"));
+    }
+    element
+}
+
+fn text_element(name: &str, value: impl std::fmt::Display) -> Element {
+    let mut element = Element::new(name);
+    element.children.push(XMLNode::Text(value.to_string()));
+    element
+}
+
+fn push(parent: &mut Element, child: Element) {
+    parent.children.push(XMLNode::Element(child));
+}
+
+fn children<'a>(element: &'a Element, name: &'a str) -> impl Iterator<Item = &'a Element> {
+    element
+        .children
+        .iter()
+        .filter_map(|node| node.as_element())
+        .filter(move |e| e.name == name)
+}
+
+fn text(element: &Element) -> Option<String> {
+    element.get_text().map(|t| t.trim().to_string())
+}
+
+fn child_text(element: &Element, name: &str) -> Option<String> {
+    element.get_child(name).and_then(text)
+}
+
+fn required<T: FromStr>(element: &Element, name: &str) -> Result<T, String> {
+    let value = match element.get_child(name) {
+        Some(child) => text(child).unwrap_or_default(),
+        None => Err(format!("<{}> without <{}>", element.name, name))?,
+    };
+
+    value
+        .parse()
+        .map_err(|_| format!("Invalid <{}> value '{}'", name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITORS_XML: &str = r#"<monitors version="2">
+  <configuration>
+    <layoutmode>physical</layoutmode>
+    <logicalmonitor>
+      <x>0</x>
+      <y>0</y>
+      <scale>2</scale>
+      <primary>yes</primary>
+      <monitor>
+        <monitorspec>
+          <connector>eDP-1</connector>
+          <vendor>BOE</vendor>
+          <product>0x0bca</product>
+          <serial>0x00000000</serial>
+        </monitorspec>
+        <mode>
+          <width>2256</width>
+          <height>1504</height>
+          <rate>59.999</rate>
+        </mode>
+      </monitor>
+    </logicalmonitor>
+    <logicalmonitor>
+      <x>2256</x>
+      <y>0</y>
+      <scale>1</scale>
+      <transform>
+        <rotation>right</rotation>
+        <flipped>no</flipped>
+      </transform>
+      <monitor>
+        <monitorspec>
+          <connector>DP-1</connector>
+          <vendor>GSM</vendor>
+          <product>LG Ultra HD</product>
+          <serial>0x0001e4f8</serial>
+        </monitorspec>
+        <mode>
+          <width>3840</width>
+          <height>2160</height>
+          <rate>59.996623992919922</rate>
+        </mode>
+        <underscanning>yes</underscanning>
+      </monitor>
+    </logicalmonitor>
+  </configuration>
+  <configuration>
+    <logicalmonitor>
+      <x>0</x>
+      <y>0</y>
+      <primary>yes</primary>
+      <monitor>
+        <monitorspec>
+          <connector>DP-1</connector>
+          <vendor>GSM</vendor>
+          <product>LG Ultra HD</product>
+          <serial>0x0001e4f8</serial>
+        </monitorspec>
+        <mode>
+          <width>1920</width>
+          <height>1080</height>
+          <rate>60</rate>
+          <flag>interlace</flag>
+        </mode>
+      </monitor>
+    </logicalmonitor>
+    <disabled>
+      <monitorspec>
+        <connector>HDMI-1</connector>
+        <vendor>SAM</vendor>
+        <product>SAMSUNG</product>
+        <serial>0x01000e00</serial>
+      </monitorspec>
+    </disabled>
+  </configuration>
+</monitors>
+"#;
+
+    #[test]
+    fn parses_configurations() {
+        let configurations = parse(MONITORS_XML).unwrap();
+        assert_eq!(2, configurations.len());
+
+        let docked = &configurations[0];
+        assert_eq!(crate::model::LayoutMode::Physical, docked.layout_mode());
+        assert_eq!(2, docked.logical_monitors.len());
+        assert_eq!(2.0, docked.logical_monitors[0].scale);
+        assert!(docked.logical_monitors[0].primary);
+        assert_eq!(3, docked.logical_monitors[1].transform);
+        assert_eq!(2256, docked.logical_monitors[1].x);
+
+        let laptop = &docked.logical_monitors[0].monitors[0];
+        assert!(laptop.properties.is_empty());
+        assert_eq!("2256x1504@59.999", laptop.mode.as_ref().unwrap().id);
+
+        let external = &docked.logical_monitors[1].monitors[0];
+        assert_eq!(Some(true), external.is_underscanning());
+        assert_eq!("3840x2160@59.997", external.mode.as_ref().unwrap().id);
+    }
+
+    #[test]
+    fn parses_disabled_monitors() {
+        let external_only = &parse(MONITORS_XML).unwrap()[1];
+
+        assert_eq!(1, external_only.logical_monitors.len());
+        assert_eq!(1, external_only.disabled_monitors.len());
+        assert_eq!("HDMI-1", external_only.disabled_monitors[0].connector);
+        assert_eq!(
+            "1920x1080i@60.000",
+            external_only.logical_monitors[0].monitors[0]
+                .mode
+                .as_ref()
+                .unwrap()
+                .id
+        );
+    }
+
+    #[test]
+    fn exports_configurations_which_can_be_imported() {
+        let configurations = parse(MONITORS_XML).unwrap();
+
+        let elements = configurations
+            .iter()
+            .map(|c| to_configuration(c).unwrap())
+            .collect();
+        let (content, replaced) = merge(None, elements).unwrap();
+
+        assert_eq!(vec![false, false], replaced);
+        assert!(content.starts_with("<monitors version=\"2\">"));
+        assert!(content.contains("<rotation>right</rotation>"));
+        assert!(content.contains("<flag>interlace</flag>"));
+
+        let exported = parse(&content).unwrap();
+        assert_eq!(configurations, exported);
+    }
+
+    #[test]
+    fn replaces_configuration_for_same_monitors_only() {
+        let existing = format!(
+            "<!-- kept -->\n{}<!-- kept after -->\n",
+            MONITORS_XML.replace(
+                "</monitors>",
+                "<!-- kept inside --><policy><stores><store>system</store></stores></policy></monitors>"
+            )
+        );
+        let mut external_only = parse(MONITORS_XML).unwrap().remove(1);
+        external_only.logical_monitors[0].x = 100;
+
+        let (content, replaced) = merge(
+            Some(&existing),
+            vec![to_configuration(&external_only).unwrap()],
+        )
+        .unwrap();
+
+        assert_eq!(vec![true], replaced);
+        assert!(content.starts_with("<!-- kept -->\n<monitors"));
+        assert!(content.contains("<!-- kept inside -->"));
+        assert!(content.ends_with("</monitors>\n<!-- kept after -->\n"));
+        assert!(content.contains("<store>system</store>"));
+        let merged = parse(&content).unwrap();
+        assert_eq!(2, merged.len());
+        assert_eq!(2256, merged[0].logical_monitors[1].x);
+        assert_eq!(100, merged[1].logical_monitors[0].x);
+    }
+
+    #[test]
+    fn refuses_to_merge_into_unsupported_version() {
+        let error = merge(Some(r#"<monitors version="1"></monitors>"#), vec![]).unwrap_err();
+        assert!(error.contains("Unsupported version"));
+    }
+
+    #[test]
+    fn rejects_unsupported_version() {
+        let error = parse(r#"<monitors version="1"></monitors>"#).unwrap_err();
+        assert!(error.contains("Unsupported version '1'"));
+    }
+
+    #[test]
+    fn reports_invalid_configuration() {
+        let error = parse(
+            r#"<monitors version="2"><configuration><logicalmonitor><x>0</x></logicalmonitor></configuration></monitors>"#,
+        )
+        .unwrap_err();
+        assert_eq!("Configuration 1: <logicalmonitor> without <y>", error);
+    }
+}
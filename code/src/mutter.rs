@@ -1,11 +1,27 @@
-use super::model::{DisplayConfigState, LogicalMonitor, Mode, Monitor, MonitorInfo, Props};
-use dbus::arg::{PropMap, RefArg};
+use super::error::Error;
+use super::model::{
+    DisplayConfigState, LogicalMonitor, Mode, Monitor, MonitorInfo, MonitorsConfig, PropValue,
+    Props,
+};
+use super::mutter_dbus::OrgGnomeMutterDisplayConfigMonitorsChanged;
+use dbus::arg::{Arg, ArgType, PropMap, RefArg, Variant};
 use dbus::blocking::{Connection, Proxy};
-use std::collections::HashMap;
-use std::time::Duration;
+use dbus::Message;
+use log::{debug, warn};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-const DESTINATION: &str = "org.gnome.Mutter.DisplayConfig";
-const PATH: &str = "/org/gnome/Mutter/DisplayConfig";
+pub const DESTINATION: &str = "org.gnome.Mutter.DisplayConfig";
+pub const INTERFACE: &str = "org.gnome.Mutter.DisplayConfig";
+pub const PATH: &str = "/org/gnome/Mutter/DisplayConfig";
+
+pub const INVALID_ARGS_ERROR: &str = "org.freedesktop.DBus.Error.InvalidArgs";
+pub const LIMITS_EXCEEDED_ERROR: &str = "org.freedesktop.DBus.Error.LimitsExceeded";
+pub const ACCESS_DENIED_ERROR: &str = "org.freedesktop.DBus.Error.AccessDenied";
+pub const NO_REPLY_ERROR: &str = "org.freedesktop.DBus.Error.NoReply";
+pub const TIMEOUT_ERROR: &str = "org.freedesktop.DBus.Error.Timeout";
 
 type GetCurrentStateResponse = (
     u32,
@@ -25,9 +41,9 @@ type GetCurrentStateResponse = (
     )>,
     PropMap,
 );
-type GetCurrentStateMonitorInfo = (String, String, String, String);
-type GetCurrentStateMode = (String, i32, i32, f64, f64, Vec<f64>, PropMap);
-type GetCurrentStateLogicalMonitor = (
+pub type GetCurrentStateMonitorInfo = (String, String, String, String);
+pub type GetCurrentStateMode = (String, i32, i32, f64, f64, Vec<f64>, PropMap);
+pub type GetCurrentStateLogicalMonitor = (
     i32,
     i32,
     f64,
@@ -36,11 +52,72 @@ type GetCurrentStateLogicalMonitor = (
     Vec<(String, String, String, String)>,
     PropMap,
 );
-type GetCurrentStateMonitor = (
+pub type GetCurrentStateMonitor = (
     (String, String, String, String),
     Vec<(String, i32, i32, f64, f64, Vec<f64>, PropMap)>,
     PropMap,
 );
+pub type ApplyMonitorsConfigMonitor<'a> = (&'a str, &'a str, PropMap);
+pub type ApplyMonitorsConfigLogicalMonitor<'a> = (
+    i32,
+    i32,
+    f64,
+    u32,
+    bool,
+    Vec<ApplyMonitorsConfigMonitor<'a>>,
+);
+
+/// Way the configuration passed to ApplyMonitorsConfig should be handled by Mutter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApplyMethod {
+    /// Only check whether configuration would be accepted, without applying it.
+    Verify,
+    Temporary,
+    Persistent,
+}
+
+impl ApplyMethod {
+    fn as_u32(&self) -> u32 {
+        match self {
+            ApplyMethod::Verify => 0,
+            ApplyMethod::Temporary => 1,
+            ApplyMethod::Persistent => 2,
+        }
+    }
+}
+
+/// Reason why Mutter refused display configuration passed to ApplyMonitorsConfig.
+#[derive(Debug, PartialEq)]
+pub enum Rejection {
+    InvalidArgs(String),
+    LimitsExceeded(String),
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::InvalidArgs(msg) => {
+                write!(f, "Mutter rejected configuration as invalid: {}", msg)
+            }
+            Rejection::LimitsExceeded(msg) => {
+                write!(f, "Configuration exceeds Mutter limits: {}", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+impl Rejection {
+    pub fn from_dbus_error(err: &dbus::Error) -> Option<Rejection> {
+        let message = err.message().unwrap_or_default().to_string();
+        match err.name() {
+            Some(INVALID_ARGS_ERROR) => Some(Rejection::InvalidArgs(message)),
+            Some(LIMITS_EXCEEDED_ERROR) => Some(Rejection::LimitsExceeded(message)),
+            _ => None,
+        }
+    }
+}
 
 impl From<&GetCurrentStateResponse> for DisplayConfigState {
     fn from(value: &GetCurrentStateResponse) -> Self {
@@ -66,7 +143,7 @@ impl From<&GetCurrentStateMonitorInfo> for MonitorInfo {
 
 impl From<&GetCurrentStateMode> for Mode {
     fn from(value: &GetCurrentStateMode) -> Self {
-        return Mode {
+        Mode {
             id: value.0.clone(),
             width: value.1,
             height: value.2,
@@ -74,7 +151,7 @@ impl From<&GetCurrentStateMode> for Mode {
             preferred_scale: value.4,
             supported_scales: value.5.clone(),
             properties: prop_map_to_props(&value.6),
-        };
+        }
     }
 }
 
@@ -102,23 +179,137 @@ impl From<&GetCurrentStateMonitor> for Monitor {
     }
 }
 
-fn prop_map_to_props(prop_map: &PropMap) -> Props {
+pub fn prop_map_to_props(prop_map: &PropMap) -> Props {
     let mut props: Props = HashMap::new();
     for (key, value) in prop_map {
-        if let Some(v) = value.as_str() {
-            props.insert(String::from(key), String::from(v));
-        } else if let Some(i) = value.as_i64() {
-            props.insert(String::from(key), format!("{}", i));
+        match ref_arg_to_prop_value(&value.0) {
+            Some(v) => {
+                props.insert(String::from(key), v);
+            }
+            None => warn!(
+                "Property '{}' of unsupported type '{}' is left out",
+                key,
+                value.0.signature()
+            ),
         }
     }
     props
 }
 
-pub fn get_current_state(
-    timeout: &Duration,
-) -> Result<DisplayConfigState, Box<dyn std::error::Error>> {
+fn ref_arg_to_prop_value(value: &dyn RefArg) -> Option<PropValue> {
+    let items = |value: &dyn RefArg| -> Option<Vec<PropValue>> {
+        value.as_iter()?.map(ref_arg_to_prop_value).collect()
+    };
+
+    match value.arg_type() {
+        ArgType::Boolean => value.as_i64().map(|v| PropValue::Bool(v != 0)),
+        ArgType::Byte => value.as_u64().map(|v| PropValue::Byte(v as u8)),
+        ArgType::Int16 => value.as_i64().map(|v| PropValue::Int16(v as i16)),
+        ArgType::UInt16 => value.as_u64().map(|v| PropValue::UInt16(v as u16)),
+        ArgType::Int32 => value.as_i64().map(|v| PropValue::Int32(v as i32)),
+        ArgType::UInt32 => value.as_u64().map(|v| PropValue::UInt32(v as u32)),
+        ArgType::Int64 => value.as_i64().map(PropValue::Int64),
+        ArgType::UInt64 => value.as_u64().map(PropValue::UInt64),
+        ArgType::Double => value.as_f64().map(PropValue::Double),
+        ArgType::String | ArgType::ObjectPath | ArgType::Signature => {
+            value.as_str().map(|v| PropValue::String(v.to_string()))
+        }
+        ArgType::Variant => ref_arg_to_prop_value(value.as_iter()?.next()?),
+        ArgType::Struct => items(value).map(PropValue::Struct),
+        ArgType::Array if value.signature().starts_with("a{s") => {
+            let mut entries = value.as_iter()?;
+            let mut props = Props::new();
+            while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+                props.insert(key.as_str()?.to_string(), ref_arg_to_prop_value(value)?);
+            }
+            Some(PropValue::Dict(props))
+        }
+        ArgType::Array if !value.signature().starts_with("a{") => {
+            items(value).map(PropValue::Array)
+        }
+        _ => None,
+    }
+}
+
+pub fn props_to_prop_map(props: &Props) -> PropMap {
+    let mut prop_map = PropMap::new();
+    for (key, value) in props {
+        match prop_value_to_ref_arg(value) {
+            Some(v) => {
+                prop_map.insert(key.clone(), Variant(v));
+            }
+            None => warn!(
+                "Property '{}' can't be sent over D-Bus and is left out: {}",
+                key, value
+            ),
+        }
+    }
+    prop_map
+}
+
+fn prop_value_to_ref_arg(value: &PropValue) -> Option<Box<dyn RefArg>> {
+    fn array<T: Arg + RefArg + 'static>(
+        items: &[PropValue],
+        get: fn(&PropValue) -> Option<T>,
+    ) -> Option<Box<dyn RefArg>> {
+        let values: Option<Vec<T>> = items.iter().map(get).collect();
+        values.map(|v| Box::new(v) as Box<dyn RefArg>)
+    }
+
+    // Array of items which all are of the variant, `None` for other items.
+    macro_rules! typed_array {
+        ($items:expr, $variant:ident) => {
+            array($items, |v| match v {
+                PropValue::$variant(v) => Some(v.clone()),
+                _ => None,
+            })?
+        };
+    }
+
+    Some(match value {
+        PropValue::Bool(v) => Box::new(*v),
+        PropValue::Byte(v) => Box::new(*v),
+        PropValue::Int16(v) => Box::new(*v),
+        PropValue::UInt16(v) => Box::new(*v),
+        PropValue::Int32(v) => Box::new(*v),
+        PropValue::UInt32(v) => Box::new(*v),
+        PropValue::Int64(v) => Box::new(*v),
+        PropValue::UInt64(v) => Box::new(*v),
+        PropValue::Double(v) => Box::new(*v),
+        PropValue::String(v) | PropValue::Legacy(v) => Box::new(v.clone()),
+        PropValue::Struct(fields) => {
+            let fields: Option<VecDeque<Box<dyn RefArg>>> =
+                fields.iter().map(prop_value_to_ref_arg).collect();
+            Box::new(fields?)
+        }
+        PropValue::Dict(entries) => {
+            let entries: Option<PropMap> = entries
+                .iter()
+                .map(|(k, v)| Some((k.clone(), Variant(prop_value_to_ref_arg(v)?))))
+                .collect();
+            Box::new(entries?)
+        }
+        // Element type of an empty array is not known, and arrays of containers can't be
+        // built from dynamically typed values.
+        PropValue::Array(items) => match items.first() {
+            Some(PropValue::Bool(_)) => typed_array!(items, Bool),
+            Some(PropValue::Byte(_)) => typed_array!(items, Byte),
+            Some(PropValue::Int16(_)) => typed_array!(items, Int16),
+            Some(PropValue::UInt16(_)) => typed_array!(items, UInt16),
+            Some(PropValue::Int32(_)) => typed_array!(items, Int32),
+            Some(PropValue::UInt32(_)) => typed_array!(items, UInt32),
+            Some(PropValue::Int64(_)) => typed_array!(items, Int64),
+            Some(PropValue::UInt64(_)) => typed_array!(items, UInt64),
+            Some(PropValue::Double(_)) => typed_array!(items, Double),
+            Some(PropValue::String(_)) => typed_array!(items, String),
+            _ => return None,
+        },
+    })
+}
+
+pub fn get_current_state(timeout: &Duration) -> Result<DisplayConfigState, Error> {
     let conn = Connection::new_session()?;
-    let proxy: Proxy<_> = conn.with_proxy(DESTINATION, PATH, timeout.clone());
+    let proxy: Proxy<_> = conn.with_proxy(DESTINATION, PATH, *timeout);
 
     use super::mutter_dbus::OrgGnomeMutterDisplayConfig;
 
@@ -127,45 +318,141 @@ pub fn get_current_state(
     Ok(DisplayConfigState::from(&current_state))
 }
 
-pub fn apply_monitors_config(
-    serial: u32,
-    persistent: bool,
-    state: &DisplayConfigState,
+/// Unique bus name of the connection owning org.gnome.Mutter.DisplayConfig.
+/// `None` when nobody provides the service.
+pub fn service_owner(timeout: &Duration) -> Result<Option<String>, Error> {
+    let conn = Connection::new_session()?;
+    let proxy: Proxy<_> =
+        conn.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", *timeout);
+
+    let (has_owner,): (bool,) =
+        proxy.method_call("org.freedesktop.DBus", "NameHasOwner", (DESTINATION,))?;
+    if !has_owner {
+        return Ok(None);
+    }
+
+    let (owner,): (String,) =
+        proxy.method_call("org.freedesktop.DBus", "GetNameOwner", (DESTINATION,))?;
+    Ok(Some(owner))
+}
+
+/// Reads property of org.gnome.Mutter.DisplayConfig interface.
+pub fn get_property(name: &str, timeout: &Duration) -> Result<PropValue, Error> {
+    let conn = Connection::new_session()?;
+    let proxy: Proxy<_> = conn.with_proxy(DESTINATION, PATH, *timeout);
+
+    use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+
+    let value: Box<dyn RefArg> = proxy.get(INTERFACE, name)?;
+
+    match ref_arg_to_prop_value(&value) {
+        Some(v) => Ok(v),
+        None => Err(format!(
+            "Property '{}' has unsupported type '{}'",
+            name,
+            value.signature()
+        ))?,
+    }
+}
+
+/// Shortest period of waiting for D-Bus messages, so that a short debounce doesn't
+/// turn the wait into busy polling.
+const MIN_PROCESS_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Blocks listening for MonitorsChanged signal and calls `on_change` once a burst of
+/// signals settles down for `debounce` period.
+pub fn watch_monitors_changed<F: FnMut()>(
+    debounce: &Duration,
     timeout: &Duration,
-) -> Result<(), Box<dyn std::error::Error>> {
+    mut on_change: F,
+) -> Result<(), Error> {
     let conn = Connection::new_session()?;
-    let proxy: Proxy<_> = conn.with_proxy(DESTINATION, PATH, timeout.clone());
+    let proxy: Proxy<_> = conn.with_proxy(DESTINATION, PATH, *timeout);
 
-    let method = if persistent { 2 } else { 1 };
-    let mut logical_monitors: Vec<(i32, i32, f64, u32, bool, Vec<(&str, &str, PropMap)>)> =
-        Vec::new();
+    let debouncer = Arc::new(Mutex::new(Debouncer::new(*debounce)));
+    let signal_debouncer = debouncer.clone();
 
-    for lm in &state.logical_monitors {
-        let mut modes: Vec<(&str, &str, PropMap)> = Vec::new();
+    proxy.match_signal(
+        move |_: OrgGnomeMutterDisplayConfigMonitorsChanged, _: &Connection, _: &Message| {
+            debug!("MonitorsChanged signal received");
+            signal_debouncer.lock().unwrap().signal(Instant::now());
+            true
+        },
+    )?;
 
-        let connectors: Vec<&String> = lm.monitors.iter().map(|m| &m.connector).collect();
+    loop {
+        conn.process((*debounce).max(MIN_PROCESS_INTERVAL))?;
 
-        for connector in connectors {
-            if let Some(monitor) = state
-                .monitors
-                .iter()
-                .find(|m| m.monitor_info.connector == *connector)
-            {
-                if let Some(current_mode_id) = monitor.get_current_mode_id() {
-                    modes.push((
-                        connector.as_str(),
-                        &current_mode_id.as_str(),
-                        PropMap::new(),
-                    ));
-                }
-            }
+        let ready = debouncer.lock().unwrap().take_ready(Instant::now());
+        if ready {
+            on_change();
+        }
+    }
+}
+
+struct Debouncer {
+    delay: Duration,
+    last_signal: Option<Instant>,
+}
+
+impl Debouncer {
+    fn new(delay: Duration) -> Debouncer {
+        Debouncer {
+            delay,
+            last_signal: None,
         }
+    }
+
+    fn signal(&mut self, now: Instant) {
+        self.last_signal = Some(now);
+    }
 
-        logical_monitors.push((lm.x, lm.y, lm.scale, lm.transform, lm.primary, modes));
+    /// Whether there was a signal and no other signal followed it during delay.
+    fn take_ready(&mut self, now: Instant) -> bool {
+        match self.last_signal {
+            Some(last) if now.duration_since(last) >= self.delay => {
+                self.last_signal = None;
+                true
+            }
+            _ => false,
+        }
     }
+}
+
+/// Sends display configuration to Mutter. When Mutter refuses configuration,
+/// [Error::Rejected] or [Error::AccessDenied] is returned.
+pub fn apply_monitors_config(
+    serial: u32,
+    method: ApplyMethod,
+    config: &MonitorsConfig,
+    timeout: &Duration,
+) -> Result<(), Error> {
+    let conn = Connection::new_session()?;
+    let proxy: Proxy<_> = conn.with_proxy(DESTINATION, PATH, *timeout);
+
+    let logical_monitors: Vec<ApplyMonitorsConfigLogicalMonitor> = config
+        .logical_monitors
+        .iter()
+        .map(|lm| {
+            let monitors = lm
+                .monitors
+                .iter()
+                .map(|m| {
+                    (
+                        m.connector.as_str(),
+                        m.mode_id.as_str(),
+                        props_to_prop_map(&m.properties),
+                    )
+                })
+                .collect();
+            (lm.x, lm.y, lm.scale, lm.transform, lm.primary, monitors)
+        })
+        .collect();
+
+    let properties = props_to_prop_map(&config.properties);
 
     use super::mutter_dbus::OrgGnomeMutterDisplayConfig;
-    proxy.apply_monitors_config(serial, method, logical_monitors, PropMap::new())?;
+    proxy.apply_monitors_config(serial, method.as_u32(), logical_monitors, properties)?;
 
     Ok(())
 }
@@ -330,4 +617,187 @@ mod tests {
             ))
         )
     }
+
+    #[test]
+    fn apply_method_values() {
+        assert_eq!(0, ApplyMethod::Verify.as_u32());
+        assert_eq!(1, ApplyMethod::Temporary.as_u32());
+        assert_eq!(2, ApplyMethod::Persistent.as_u32());
+    }
+
+    #[test]
+    fn rejection_from_dbus_error() {
+        assert_eq!(
+            Some(Rejection::InvalidArgs("Invalid mode".to_string())),
+            Rejection::from_dbus_error(&dbus::Error::new_custom(
+                INVALID_ARGS_ERROR,
+                "Invalid mode"
+            ))
+        );
+        assert_eq!(
+            Some(Rejection::LimitsExceeded("Too big".to_string())),
+            Rejection::from_dbus_error(&dbus::Error::new_custom(LIMITS_EXCEEDED_ERROR, "Too big"))
+        );
+        assert_eq!(
+            None,
+            Rejection::from_dbus_error(&dbus::Error::new_custom(
+                "org.freedesktop.DBus.Error.AccessDenied",
+                "Stale serial"
+            ))
+        );
+    }
+
+    #[test]
+    fn monitor_underscanning_from_dbus() {
+        let monitor_info_dbus = (
+            "HDMI-1".to_string(),
+            "V".to_string(),
+            "P".to_string(),
+            "S".to_string(),
+        );
+        let mut properties = PropMap::new();
+        properties.insert("is-underscanning".to_string(), Variant(Box::new(true)));
+
+        let monitor = Monitor::from(&(monitor_info_dbus, vec![], properties));
+
+        assert_eq!(Some(true), monitor.is_underscanning());
+    }
+
+    #[test]
+    fn props_from_dbus() {
+        let mut prop_map = PropMap::new();
+        prop_map.insert("is-builtin".to_string(), Variant(Box::new(true)));
+        prop_map.insert("layout-mode".to_string(), Variant(Box::new(2u32)));
+        prop_map.insert(
+            "display-name".to_string(),
+            Variant(Box::new("Built-in display".to_string())),
+        );
+        prop_map.insert(
+            "max-screen-size".to_string(),
+            Variant(Box::new((16384i32, 8192i32))),
+        );
+        prop_map.insert(
+            "privacy-screen-state".to_string(),
+            Variant(Box::new((true, false))),
+        );
+
+        let props = prop_map_to_props(&prop_map);
+
+        assert_eq!(
+            Props::from([
+                ("is-builtin".to_string(), PropValue::Bool(true)),
+                ("layout-mode".to_string(), PropValue::UInt32(2)),
+                (
+                    "display-name".to_string(),
+                    PropValue::String("Built-in display".to_string())
+                ),
+                (
+                    "max-screen-size".to_string(),
+                    PropValue::Struct(vec![PropValue::Int32(16384), PropValue::Int32(8192)])
+                ),
+                (
+                    "privacy-screen-state".to_string(),
+                    PropValue::Struct(vec![PropValue::Bool(true), PropValue::Bool(false)])
+                ),
+            ]),
+            props
+        );
+    }
+
+    #[test]
+    fn props_to_dbus_round_trip() {
+        let props = Props::from([
+            ("enable_underscanning".to_string(), PropValue::Bool(true)),
+            ("layout-mode".to_string(), PropValue::UInt32(1)),
+            (
+                "max-screen-size".to_string(),
+                PropValue::Struct(vec![PropValue::Int32(16384), PropValue::Int32(8192)]),
+            ),
+            (
+                "scales".to_string(),
+                PropValue::Array(vec![PropValue::Double(1.0), PropValue::Double(2.0)]),
+            ),
+        ]);
+
+        let prop_map = props_to_prop_map(&props);
+
+        assert_eq!(
+            "b",
+            prop_map["enable_underscanning"].0.signature().to_string()
+        );
+        assert_eq!("u", prop_map["layout-mode"].0.signature().to_string());
+        assert_eq!(
+            "(ii)",
+            prop_map["max-screen-size"].0.signature().to_string()
+        );
+        assert_eq!("ad", prop_map["scales"].0.signature().to_string());
+        assert_eq!(props, prop_map_to_props(&prop_map));
+    }
+
+    #[test]
+    fn dict_and_integer_arrays_round_trip() {
+        let props = Props::from([
+            (
+                "edid".to_string(),
+                PropValue::Array(vec![PropValue::Byte(0), PropValue::Byte(255)]),
+            ),
+            (
+                "sizes".to_string(),
+                PropValue::Array(vec![PropValue::Int64(-1), PropValue::Int64(1 << 40)]),
+            ),
+            (
+                "details".to_string(),
+                PropValue::Dict(Props::from([
+                    ("serial".to_string(), PropValue::UInt32(7)),
+                    ("name".to_string(), PropValue::String("TV".to_string())),
+                ])),
+            ),
+        ]);
+
+        let prop_map = props_to_prop_map(&props);
+
+        assert_eq!("ay", prop_map["edid"].0.signature().to_string());
+        assert_eq!("ax", prop_map["sizes"].0.signature().to_string());
+        assert_eq!("a{sv}", prop_map["details"].0.signature().to_string());
+        assert_eq!(props, prop_map_to_props(&prop_map));
+    }
+
+    #[test]
+    fn rejects_arrays_without_known_element_type() {
+        let props = Props::from([
+            ("empty".to_string(), PropValue::Array(vec![])),
+            (
+                "pairs".to_string(),
+                PropValue::Array(vec![PropValue::Struct(vec![PropValue::Int32(1)])]),
+            ),
+            (
+                "mixed".to_string(),
+                PropValue::Array(vec![PropValue::Int32(1), PropValue::Byte(1)]),
+            ),
+            (
+                "nested".to_string(),
+                PropValue::Dict(Props::from([(
+                    "empty".to_string(),
+                    PropValue::Array(vec![]),
+                )])),
+            ),
+        ]);
+
+        assert!(props_to_prop_map(&props).is_empty());
+    }
+
+    #[test]
+    fn debouncer_waits_for_burst_to_settle() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut debouncer = Debouncer::new(ms(500));
+
+        assert!(!debouncer.take_ready(start));
+
+        debouncer.signal(start);
+        debouncer.signal(start + ms(300));
+        assert!(!debouncer.take_ready(start + ms(600)));
+        assert!(debouncer.take_ready(start + ms(800)));
+        assert!(!debouncer.take_ready(start + ms(1500)));
+    }
 }
@@ -1,5 +1,133 @@
-use super::model::Preset;
-use std::collections::HashMap;
+use super::diff::{DiffLine, PresetChange};
+use super::doctor::{Check, Status};
+use super::history::HistoryEntry;
+use super::model::{Preset, PresetMode, SelectionRules};
+use super::selection::Evaluation;
+use super::validation::Verdict;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn print_verdict(preset_name: &str, verdict: &Verdict) {
+    match verdict {
+        Verdict::Accepted => println!("Preset '{}': OK", preset_name),
+        Verdict::Rejected { cause, issues } => {
+            println!("Preset '{}': REJECTED", preset_name);
+            println!("{}", cause);
+            for issue in issues {
+                println!("{}: {}", issue.connector, issue.reason);
+            }
+        }
+    }
+}
+
+pub fn print_checks(checks: &[Check]) {
+    for check in checks {
+        let status = match check.status {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        };
+        println!("[{}] {}: {}", status, check.name, check.message);
+    }
+}
+
+pub fn print_checks_json(checks: &[Check]) {
+    #[derive(Serialize)]
+    struct Report<'a> {
+        checks: &'a [Check],
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&Report { checks }).unwrap()
+    );
+}
+
+/// Prints presets which are overwritten or removed. Added presets are only listed.
+pub fn print_changes(changes: &[PresetChange]) {
+    for change in changes {
+        match change {
+            PresetChange::Added(preset) => println!("Preset '{}' added", preset.name),
+            PresetChange::Removed(preset) => println!("Preset '{}' removed", preset.name),
+            PresetChange::Changed { name, lines } => {
+                println!("Preset '{}' changed:", name);
+                for line in lines {
+                    match line {
+                        DiffLine::Removed(line) => println!("-{}", line),
+                        DiffLine::Added(line) => println!("+{}", line),
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn print_history(entries: &[HistoryEntry]) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    for (i, entry) in entries.iter().enumerate() {
+        println!(
+            "{:>3}  {:<16}  {:>3} preset(s)  {}",
+            i,
+            format_age(now.saturating_sub(entry.timestamp)),
+            entry.configuration.presets.len(),
+            entry.command
+        );
+    }
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} min ago", seconds / 60),
+        3600..86400 => format!("{} hours ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+pub fn print_rules(preset_name: &str, rules: &Option<SelectionRules>) {
+    println!("Preset: '{}'", preset_name);
+
+    match rules {
+        Some(rules) => {
+            println!("Priority: {}", rules.priority);
+            println!("Conditions:");
+            for condition in &rules.conditions {
+                println!("  {}", condition);
+            }
+        }
+        None => println!("No rules. Selected when same monitors are connected as saved."),
+    }
+}
+
+pub fn print_evaluations(evaluations: &[Evaluation], winner: Option<&Evaluation>) {
+    for evaluation in evaluations {
+        let satisfied = evaluation.results.iter().filter(|(_, ok)| *ok).count();
+        let status = if winner.is_some_and(|w| std::ptr::eq(w, evaluation)) {
+            "SELECTED"
+        } else if evaluation.is_eligible() {
+            "ELIGIBLE"
+        } else {
+            "NOT ELIGIBLE"
+        };
+
+        println!(
+            "Preset '{}': {} (priority {}, {}/{} conditions satisfied)",
+            evaluation.preset.name,
+            status,
+            evaluation.priority,
+            satisfied,
+            evaluation.results.len()
+        );
+
+        for (condition, ok) in &evaluation.results {
+            println!("  [{}] {}", if *ok { "x" } else { " " }, condition);
+        }
+    }
+}
 
 pub fn print_preset(preset: &Preset) {
     println!("Preset: '{}'", preset.name);
@@ -10,33 +138,32 @@ pub fn print_preset(preset: &Preset) {
     println!("Physical displays:");
     println!();
 
-    let mut current_mode_by_connector = HashMap::<String, String>::new();
-
-    for monitor in &display_config.monitors {
+    for monitor in display_config.monitors() {
         println!("{}:", monitor.monitor_info.connector);
         println!("Vendor: {}", monitor.monitor_info.vendor);
         println!("Model: {}", monitor.monitor_info.product);
-        print!("Supported modes: ");
-
-        for mode in &monitor.modes {
-            if let Some(v) = mode.properties.get("is-current") {
-                if v == "1" {
-                    current_mode_by_connector.insert(
-                        monitor.monitor_info.connector.clone(),
-                        format!("{}x{}@{}", mode.width, mode.height, mode.refresh_rate),
-                    );
-                }
-            }
-            print!("{}x{}@{} ", mode.width, mode.height, mode.refresh_rate);
+        if let Some(underscanning) = monitor.is_underscanning() {
+            println!("Underscanning: {}", underscanning);
         }
+        println!("Mode: {}", format_mode(monitor.mode.as_ref()));
         println!();
+    }
+
+    for info in &display_config.disabled_monitors {
+        println!("{}: disabled", info.connector);
+        println!("Vendor: {}", info.vendor);
+        println!("Model: {}", info.product);
         println!();
     }
 
     println!("Logical displays:");
     println!();
     for lm in &display_config.logical_monitors {
-        let connectors: Vec<&String> = lm.monitors.iter().map(|m| &m.connector).collect();
+        let connectors: Vec<&String> = lm
+            .monitors
+            .iter()
+            .map(|m| &m.monitor_info.connector)
+            .collect();
         println!("Connectors: {:?}", connectors);
         println!("X: {}", lm.x);
         println!("Y: {}", lm.y);
@@ -45,16 +172,32 @@ pub fn print_preset(preset: &Preset) {
         println!("Transform: {}", lm.transform);
         println!("Mode:");
 
-        for connector in connectors {
+        for monitor in &lm.monitors {
             println!(
                 "{}: {}",
-                connector,
-                current_mode_by_connector
-                    .get(connector)
-                    .unwrap_or(&"".to_string())
+                monitor.monitor_info.connector,
+                format_mode(monitor.mode.as_ref())
             );
         }
 
         println!();
     }
 }
+
+fn format_mode(mode: Option<&PresetMode>) -> String {
+    mode.map(|m| format!("{}x{}@{}", m.width, m.height, m.refresh_rate))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_age() {
+        assert_eq!("just now", format_age(59));
+        assert_eq!("5 min ago", format_age(300));
+        assert_eq!("2 hours ago", format_age(7200));
+        assert_eq!("3 days ago", format_age(3 * 86400 + 5));
+    }
+}
@@ -0,0 +1,45 @@
+use std::io::Write;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Asks user a yes/no question on the terminal. Returns `false` when user declines,
+/// when input is closed or when there is no answer within timeout.
+pub fn confirm(question: &str, timeout: &Duration) -> bool {
+    print!("{} [y/N] ", question);
+    let _ = std::io::stdout().flush();
+
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut answer = String::new();
+        let result = std::io::stdin().read_line(&mut answer).map(|_| answer);
+        let _ = sender.send(result);
+    });
+
+    match receiver.recv_timeout(*timeout) {
+        Ok(Ok(answer)) => is_yes(&answer),
+        _ => {
+            println!();
+            false
+        }
+    }
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_yes_answers() {
+        assert!(is_yes("y\n"));
+        assert!(is_yes("Yes\n"));
+        assert!(!is_yes("\n"));
+        assert!(!is_yes("n\n"));
+        assert!(!is_yes(""));
+    }
+}
@@ -0,0 +1,626 @@
+use super::model::{
+    DisplayConfigState, LayoutMode, LogicalMonitorConfig, Mode, Monitor, MonitorConfig,
+    MonitorInfo, MonitorsConfig, PresetConfig, PresetMode, PropValue, Props,
+};
+use log::{debug, warn};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Default)]
+pub struct ResolveOptions {
+    /// Fail instead of substituting saved modes which are not supported by monitors anymore.
+    pub strict: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ResolveError {
+    /// Displays required by the preset which are not connected at the moment.
+    MissingMonitors(Vec<MonitorInfo>),
+    /// Preset does not contain current mode for the monitor.
+    MissingMode(MonitorInfo),
+    /// Saved mode is not supported by the monitor anymore and substitution is not allowed.
+    ModeUnavailable {
+        monitor: MonitorInfo,
+        mode_id: String,
+    },
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::MissingMonitors(monitors) => {
+                let names: Vec<String> = monitors.iter().map(|m| m.describe()).collect();
+                write!(
+                    f,
+                    "Displays required by preset are not connected: {}",
+                    names.join(", ")
+                )
+            }
+            ResolveError::MissingMode(monitor) => write!(
+                f,
+                "Preset does not contain current mode for display {}",
+                monitor.describe()
+            ),
+            ResolveError::ModeUnavailable { monitor, mode_id } => write!(
+                f,
+                "Mode '{}' is not supported by display {} anymore",
+                mode_id,
+                monitor.describe()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Maps monitors of the preset onto currently connected monitors. Monitors are matched
+/// by vendor, product and serial first, so the preset follows displays which were
+/// plugged into different connectors. Connector name is used to choose between identical
+/// displays and for displays which don't report their identity.
+///
+/// When saved mode is not advertised by the monitor anymore, the closest available mode
+/// is used instead, unless [ResolveOptions::strict] is set.
+///
+/// Layout mode of the preset is restored when the session supports changing it.
+/// Otherwise logical monitor positions are translated to the layout mode of the session.
+pub fn resolve_preset(
+    preset: &PresetConfig,
+    current: &DisplayConfigState,
+    options: &ResolveOptions,
+) -> Result<MonitorsConfig, ResolveError> {
+    let preset_monitors: Vec<&MonitorInfo> = preset.monitors().map(|m| &m.monitor_info).collect();
+
+    let assignment = assign_monitors(&preset_monitors, current);
+
+    let missing: Vec<MonitorInfo> = preset_monitors
+        .iter()
+        .zip(&assignment)
+        .filter(|(_, assigned)| assigned.is_none())
+        .map(|(info, _)| (*info).clone())
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(ResolveError::MissingMonitors(missing));
+    }
+
+    let mut assigned = assignment.into_iter().flatten();
+    let mut logical_monitors = Vec::new();
+    let mut mode_sizes = Vec::new();
+
+    for lm in &preset.logical_monitors {
+        let mut monitors = Vec::new();
+        let mut mode_size = (0, 0);
+
+        for saved_monitor in &lm.monitors {
+            let info = &saved_monitor.monitor_info;
+            let current_monitor = assigned.next().unwrap();
+
+            let saved_mode = saved_monitor
+                .mode
+                .as_ref()
+                .ok_or_else(|| ResolveError::MissingMode(info.clone()))?;
+
+            let mode_id = resolve_mode(info, saved_mode, current_monitor, options)?;
+
+            if let Some(mode) = current_monitor.modes.iter().find(|m| m.id == mode_id) {
+                mode_size = if lm.transform % 2 == 1 {
+                    (mode.height, mode.width)
+                } else {
+                    (mode.width, mode.height)
+                };
+            }
+
+            if current_monitor.monitor_info.connector != info.connector {
+                debug!(
+                    "Display {} is now connected to {}",
+                    info.describe(),
+                    current_monitor.monitor_info.connector
+                );
+            }
+
+            let mut properties = Props::new();
+            if let Some(underscanning) =
+                resolve_underscanning(info, saved_monitor.is_underscanning(), current_monitor)
+            {
+                properties.insert(
+                    "enable_underscanning".to_string(),
+                    PropValue::Bool(underscanning),
+                );
+            }
+
+            monitors.push(MonitorConfig {
+                connector: current_monitor.monitor_info.connector.clone(),
+                mode_id,
+                properties,
+            });
+        }
+
+        logical_monitors.push(LogicalMonitorConfig {
+            x: lm.x,
+            y: lm.y,
+            scale: lm.scale,
+            transform: lm.transform,
+            primary: lm.primary,
+            monitors,
+        });
+        mode_sizes.push(mode_size);
+    }
+
+    let current_layout_mode = current.layout_mode();
+
+    let mut properties = Props::new();
+
+    // Positions of a preset without layout mode, e.g. imported from monitors.xml, are
+    // used as they are in the layout mode of the session.
+    match preset.layout_mode {
+        Some(saved_layout_mode) if current.supports_changing_layout_mode() => {
+            properties.insert(
+                "layout-mode".to_string(),
+                PropValue::UInt32(saved_layout_mode.as_u32()),
+            );
+        }
+        Some(saved_layout_mode) if saved_layout_mode != current_layout_mode => {
+            warn!(
+                "Preset was saved in {:?} layout mode, which can't be used in current session. \
+                Positions of logical monitors are translated to {:?} layout mode",
+                saved_layout_mode, current_layout_mode
+            );
+            translate_layout(
+                &mut logical_monitors,
+                &mode_sizes,
+                saved_layout_mode,
+                current_layout_mode,
+            );
+        }
+        _ => {}
+    }
+
+    Ok(MonitorsConfig {
+        logical_monitors,
+        properties,
+    })
+}
+
+/// Recalculates positions of logical monitors laid out in one layout mode, so that
+/// they keep adjacent to each other in another layout mode.
+pub fn translate_layout(
+    logical_monitors: &mut [LogicalMonitorConfig],
+    mode_sizes: &[(i32, i32)],
+    from: LayoutMode,
+    to: LayoutMode,
+) {
+    let size_in = |layout_mode: LayoutMode| -> Vec<(i32, i32)> {
+        logical_monitors
+            .iter()
+            .zip(mode_sizes)
+            .map(|(lm, (width, height))| match layout_mode {
+                LayoutMode::Physical => (*width, *height),
+                LayoutMode::Logical => (
+                    (*width as f64 / lm.scale).round() as i32,
+                    (*height as f64 / lm.scale).round() as i32,
+                ),
+            })
+            .collect()
+    };
+
+    let source_sizes = size_in(from);
+    let target_sizes = size_in(to);
+
+    let xs = translate_axis(
+        &logical_monitors.iter().map(|lm| lm.x).collect::<Vec<_>>(),
+        &source_sizes.iter().map(|s| s.0).collect::<Vec<_>>(),
+        &target_sizes.iter().map(|s| s.0).collect::<Vec<_>>(),
+    );
+    let ys = translate_axis(
+        &logical_monitors.iter().map(|lm| lm.y).collect::<Vec<_>>(),
+        &source_sizes.iter().map(|s| s.1).collect::<Vec<_>>(),
+        &target_sizes.iter().map(|s| s.1).collect::<Vec<_>>(),
+    );
+
+    for (lm, (x, y)) in logical_monitors.iter_mut().zip(xs.into_iter().zip(ys)) {
+        lm.x = x;
+        lm.y = y;
+    }
+}
+
+fn translate_axis(starts: &[i32], source_lengths: &[i32], target_lengths: &[i32]) -> Vec<i32> {
+    let mut order: Vec<usize> = (0..starts.len()).collect();
+    order.sort_by_key(|i| starts[*i]);
+
+    let min_start = starts.iter().copied().min().unwrap_or(0);
+    let mut result: Vec<Option<i32>> = vec![None; starts.len()];
+
+    for &i in &order {
+        let adjacent = order
+            .iter()
+            .find(|&&j| result[j].is_some() && starts[j] + source_lengths[j] == starts[i]);
+
+        result[i] = Some(match adjacent {
+            _ if starts[i] == min_start => starts[i],
+            Some(&j) => result[j].unwrap() + target_lengths[j],
+            None if source_lengths[i] > 0 => (starts[i] as f64 * target_lengths[i] as f64
+                / source_lengths[i] as f64)
+                .round() as i32,
+            None => starts[i],
+        });
+    }
+
+    result.into_iter().flatten().collect()
+}
+
+fn resolve_mode(
+    info: &MonitorInfo,
+    saved_mode: &PresetMode,
+    monitor: &Monitor,
+    options: &ResolveOptions,
+) -> Result<String, ResolveError> {
+    if monitor.modes.iter().any(|m| m.id == saved_mode.id) {
+        return Ok(saved_mode.id.clone());
+    }
+
+    let closest = closest_mode(saved_mode, &monitor.modes);
+
+    match closest {
+        Some(closest) if !options.strict => {
+            warn!(
+                "Mode '{}' is not supported by display {} anymore. Using closest mode '{}' instead",
+                saved_mode.id,
+                info.describe(),
+                closest.id
+            );
+            Ok(closest.id.clone())
+        }
+        _ => Err(ResolveError::ModeUnavailable {
+            monitor: info.clone(),
+            mode_id: saved_mode.id.clone(),
+        }),
+    }
+}
+
+fn resolve_underscanning(
+    info: &MonitorInfo,
+    saved: Option<bool>,
+    monitor: &Monitor,
+) -> Option<bool> {
+    match (saved, monitor.is_underscanning()) {
+        (Some(saved), Some(_)) => Some(saved),
+        (Some(true), None) => {
+            warn!(
+                "Display {} does not support underscanning anymore",
+                info.describe()
+            );
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Finds mode closest to the given one by resolution, then by refresh rate,
+/// then by interlacing.
+fn closest_mode<'a>(target: &PresetMode, modes: &'a [Mode]) -> Option<&'a Mode> {
+    let distance = |m: &Mode| {
+        (
+            (m.width - target.width).abs() + (m.height - target.height).abs(),
+            (m.refresh_rate - target.refresh_rate).abs(),
+            m.is_interlaced() != target.interlaced,
+        )
+    };
+
+    modes.iter().min_by(|a, b| {
+        let (a, b) = (distance(a), distance(b));
+        a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)).then(a.2.cmp(&b.2))
+    })
+}
+
+fn assign_monitors<'a>(
+    preset_monitors: &[&MonitorInfo],
+    current: &'a DisplayConfigState,
+) -> Vec<Option<&'a Monitor>> {
+    let mut taken = vec![false; current.monitors.len()];
+    let mut assignment: Vec<Option<&Monitor>> = vec![None; preset_monitors.len()];
+
+    let mut claim = |matches: &dyn Fn(&MonitorInfo, &MonitorInfo) -> bool,
+                     assignment: &mut Vec<Option<&'a Monitor>>| {
+        for (i, info) in preset_monitors.iter().enumerate() {
+            if assignment[i].is_some() {
+                continue;
+            }
+            let found = current
+                .monitors
+                .iter()
+                .enumerate()
+                .find(|(j, m)| !taken[*j] && matches(info, &m.monitor_info));
+            if let Some((j, monitor)) = found {
+                taken[j] = true;
+                assignment[i] = Some(monitor);
+            }
+        }
+    };
+
+    // Same display on the same connector
+    claim(
+        &|saved, live| saved.same_hardware(live) && saved.connector == live.connector,
+        &mut assignment,
+    );
+    // Same display moved to another connector
+    claim(&|saved, live| saved.same_hardware(live), &mut assignment);
+    // Display without identity, recognized by connector only
+    claim(
+        &|saved, live| {
+            !saved.has_identity() && !live.has_identity() && saved.connector == live.connector
+        },
+        &mut assignment,
+    );
+
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{info, mode, state, with_current_mode};
+    use crate::model::PropValue;
+
+    fn connectors(config: &MonitorsConfig) -> Vec<&str> {
+        config
+            .logical_monitors
+            .iter()
+            .flat_map(|lm| lm.monitors.iter().map(|m| m.connector.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_connectors_when_unchanged() {
+        let preset = state(vec![
+            with_current_mode(info("DP-3", "1"), mode("A", 1920, 1080, 60.0)),
+            with_current_mode(info("DP-4", "2"), mode("B", 1920, 1080, 60.0)),
+        ]);
+
+        let config = resolve_preset(
+            &PresetConfig::from_state(&preset),
+            &preset,
+            &ResolveOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(vec!["DP-3", "DP-4"], connectors(&config));
+        assert_eq!("A", config.logical_monitors[0].monitors[0].mode_id);
+    }
+
+    #[test]
+    fn follows_displays_to_new_connectors() {
+        let preset = state(vec![
+            with_current_mode(info("DP-3", "1"), mode("A", 1920, 1080, 60.0)),
+            with_current_mode(info("DP-4", "2"), mode("B", 1920, 1080, 60.0)),
+        ]);
+        let current = state(vec![
+            with_current_mode(info("DP-4", "1"), mode("A", 1920, 1080, 60.0)),
+            with_current_mode(info("DP-5", "2"), mode("B", 1920, 1080, 60.0)),
+        ]);
+
+        let config = resolve_preset(
+            &PresetConfig::from_state(&preset),
+            &current,
+            &ResolveOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(vec!["DP-4", "DP-5"], connectors(&config));
+    }
+
+    #[test]
+    fn prefers_same_connector_for_identical_displays() {
+        // Same vendor and product without serial numbers.
+        let info = |connector: &str| MonitorInfo {
+            serial: String::new(),
+            ..info(connector, "1")
+        };
+        let preset = state(vec![
+            with_current_mode(info("DP-3"), mode("A", 1920, 1080, 60.0)),
+            with_current_mode(info("DP-4"), mode("B", 1920, 1080, 60.0)),
+        ]);
+        let current = state(vec![
+            with_current_mode(info("DP-4"), mode("B", 1920, 1080, 60.0)),
+            with_current_mode(info("DP-3"), mode("A", 1920, 1080, 60.0)),
+        ]);
+
+        let config = resolve_preset(
+            &PresetConfig::from_state(&preset),
+            &current,
+            &ResolveOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(vec!["DP-3", "DP-4"], connectors(&config));
+    }
+
+    #[test]
+    fn fails_when_display_is_missing() {
+        let preset = state(vec![
+            with_current_mode(info("DP-3", "1"), mode("A", 1920, 1080, 60.0)),
+            with_current_mode(info("DP-4", "2"), mode("B", 1920, 1080, 60.0)),
+        ]);
+        let current = state(vec![
+            with_current_mode(info("DP-3", "1"), mode("A", 1920, 1080, 60.0)),
+            with_current_mode(info("DP-4", "3"), mode("B", 1920, 1080, 60.0)),
+        ]);
+
+        assert_eq!(
+            Err(ResolveError::MissingMonitors(vec![info("DP-4", "2")])),
+            resolve_preset(
+                &PresetConfig::from_state(&preset),
+                &current,
+                &ResolveOptions::default()
+            )
+        );
+    }
+
+    fn interlaced(mut mode: Mode) -> Mode {
+        mode.properties
+            .insert("is-interlaced".to_string(), PropValue::Bool(true));
+        mode
+    }
+
+    #[test]
+    fn substitutes_missing_mode_with_closest() {
+        let preset = state(vec![with_current_mode(
+            info("DP-3", "1"),
+            mode("1920x1080@60.000", 1920, 1080, 60.0),
+        )]);
+        let mut current = state(vec![with_current_mode(
+            info("DP-3", "1"),
+            mode("1920x1080@60.000", 1920, 1080, 60.0),
+        )]);
+        current.monitors[0].modes = vec![
+            mode("2560x1440@59.951", 2560, 1440, 59.951),
+            mode("1920x1080@50.000", 1920, 1080, 50.0),
+            interlaced(mode("1920x1080@59.940i", 1920, 1080, 59.94)),
+            mode("1920x1080@59.940", 1920, 1080, 59.94),
+        ];
+
+        let config = resolve_preset(
+            &PresetConfig::from_state(&preset),
+            &current,
+            &ResolveOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "1920x1080@59.940",
+            config.logical_monitors[0].monitors[0].mode_id
+        );
+    }
+
+    #[test]
+    fn fails_to_substitute_mode_in_strict_mode() {
+        let preset = state(vec![with_current_mode(
+            info("DP-3", "1"),
+            mode("1920x1080@60.000", 1920, 1080, 60.0),
+        )]);
+        let mut current = state(vec![with_current_mode(
+            info("DP-3", "1"),
+            mode("1920x1080@60.000", 1920, 1080, 60.0),
+        )]);
+        current.monitors[0].modes = vec![mode("1920x1080@59.940", 1920, 1080, 59.94)];
+
+        assert_eq!(
+            Err(ResolveError::ModeUnavailable {
+                monitor: info("DP-3", "1"),
+                mode_id: "1920x1080@60.000".to_string(),
+            }),
+            resolve_preset(
+                &PresetConfig::from_state(&preset),
+                &current,
+                &ResolveOptions { strict: true }
+            )
+        );
+    }
+
+    fn scaled_state(layout_mode: LayoutMode, supports_changing: bool) -> DisplayConfigState {
+        let mut state = state(vec![
+            with_current_mode(info("eDP-1", "1"), mode("A", 1920, 1080, 60.0)),
+            with_current_mode(info("DP-1", "2"), mode("B", 1920, 1080, 60.0)),
+        ]);
+        for (monitor, lm) in state.monitors.iter_mut().zip(&mut state.logical_monitors) {
+            monitor.modes[0].width = 3840;
+            monitor.modes[0].height = 2160;
+            lm.scale = 2.0;
+        }
+        state.logical_monitors[1].x = 3840;
+        state.properties.insert(
+            "layout-mode".to_string(),
+            PropValue::UInt32(layout_mode.as_u32()),
+        );
+        state.properties.insert(
+            "supports-changing-layout-mode".to_string(),
+            PropValue::Bool(supports_changing),
+        );
+        state
+    }
+
+    #[test]
+    fn restores_layout_mode_when_supported() {
+        let preset = scaled_state(LayoutMode::Physical, true);
+        let current = scaled_state(LayoutMode::Logical, true);
+
+        let config = resolve_preset(
+            &PresetConfig::from_state(&preset),
+            &current,
+            &ResolveOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(&PropValue::UInt32(LayoutMode::Physical.as_u32())),
+            config.properties.get("layout-mode")
+        );
+        assert_eq!(3840, config.logical_monitors[1].x);
+    }
+
+    #[test]
+    fn translates_positions_when_layout_mode_cannot_be_changed() {
+        let preset = scaled_state(LayoutMode::Physical, true);
+        let current = scaled_state(LayoutMode::Logical, false);
+
+        let config = resolve_preset(
+            &PresetConfig::from_state(&preset),
+            &current,
+            &ResolveOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(None, config.properties.get("layout-mode"));
+        assert_eq!(0, config.logical_monitors[0].x);
+        assert_eq!(1920, config.logical_monitors[1].x);
+        assert_eq!(0, config.logical_monitors[1].y);
+    }
+
+    #[test]
+    fn keeps_session_layout_mode_for_preset_without_it() {
+        let mut preset = PresetConfig::from_state(&scaled_state(LayoutMode::Logical, true));
+        preset.layout_mode = None;
+
+        for supports_changing in [true, false] {
+            let current = scaled_state(LayoutMode::Physical, supports_changing);
+
+            let config = resolve_preset(&preset, &current, &ResolveOptions::default()).unwrap();
+
+            assert_eq!(None, config.properties.get("layout-mode"));
+            assert_eq!(3840, config.logical_monitors[1].x);
+        }
+    }
+
+    #[test]
+    fn restores_underscanning_when_supported() {
+        let mut preset = state(vec![
+            with_current_mode(info("HDMI-1", "1"), mode("A", 1920, 1080, 60.0)),
+            with_current_mode(info("HDMI-2", "2"), mode("B", 1920, 1080, 60.0)),
+        ]);
+        for monitor in &mut preset.monitors {
+            monitor
+                .properties
+                .insert("is-underscanning".to_string(), PropValue::Bool(true));
+        }
+        let mut current = state(vec![
+            with_current_mode(info("HDMI-1", "1"), mode("A", 1920, 1080, 60.0)),
+            with_current_mode(info("HDMI-2", "2"), mode("B", 1920, 1080, 60.0)),
+        ]);
+        current.monitors[0]
+            .properties
+            .insert("is-underscanning".to_string(), PropValue::Bool(false));
+
+        let config = resolve_preset(
+            &PresetConfig::from_state(&preset),
+            &current,
+            &ResolveOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(&PropValue::Bool(true)),
+            config.logical_monitors[0].monitors[0]
+                .properties
+                .get("enable_underscanning")
+        );
+        assert!(config.logical_monitors[1].monitors[0].properties.is_empty());
+    }
+}
@@ -0,0 +1,292 @@
+use super::model::{
+    Condition, Configuration, DisplayConfigState, MonitorInfo, Preset, PresetConfig, SelectionRules,
+};
+use log::warn;
+
+/// Identifies physical monitors of the configuration. Monitors are identified by vendor,
+/// product and serial, or by connector when they don't report their identity.
+pub fn monitor_set(state: &DisplayConfigState) -> Vec<String> {
+    sorted_keys(state.monitors.iter().map(|m| &m.monitor_info))
+}
+
+/// Monitors the preset was saved with, both enabled and disabled, see [monitor_set].
+fn saved_monitor_set(config: &PresetConfig) -> Vec<String> {
+    sorted_keys(
+        config
+            .monitors()
+            .map(|m| &m.monitor_info)
+            .chain(&config.disabled_monitors),
+    )
+}
+
+fn sorted_keys<'a>(monitors: impl Iterator<Item = &'a MonitorInfo>) -> Vec<String> {
+    let mut keys: Vec<String> = monitors.map(monitor_key).collect();
+    keys.sort();
+    keys
+}
+
+fn monitor_key(info: &MonitorInfo) -> String {
+    if info.has_identity() {
+        format!("{}:{}:{}", info.vendor, info.product, info.serial)
+    } else {
+        info.connector.clone()
+    }
+}
+
+/// Result of evaluating rules of a preset against currently connected monitors.
+#[derive(Debug)]
+pub struct Evaluation<'a> {
+    pub preset: &'a Preset,
+    pub priority: i32,
+    pub results: Vec<(Condition, bool)>,
+}
+
+impl Evaluation<'_> {
+    /// Whether all conditions of the preset are satisfied.
+    pub fn is_eligible(&self) -> bool {
+        self.results.iter().all(|(_, satisfied)| *satisfied)
+    }
+}
+
+/// Evaluates selection rules of every preset against current display configuration.
+/// Presets without rules are evaluated as if they required the same monitors
+/// they were saved with.
+pub fn evaluate<'a>(
+    configuration: &'a Configuration,
+    current: &DisplayConfigState,
+) -> Vec<Evaluation<'a>> {
+    configuration
+        .presets
+        .iter()
+        .map(|preset| {
+            let rules = preset.rules.clone().unwrap_or_else(|| SelectionRules {
+                priority: 0,
+                conditions: vec![Condition::SameMonitors],
+            });
+
+            let results = rules
+                .conditions
+                .into_iter()
+                .map(|c| {
+                    let satisfied = is_satisfied(&c, preset, current);
+                    (c, satisfied)
+                })
+                .collect();
+
+            Evaluation {
+                preset,
+                priority: rules.priority,
+                results,
+            }
+        })
+        .collect()
+}
+
+/// Chooses eligible preset with the highest priority. Ties are broken in favor of
+/// the preset with more conditions, then by the order of presets.
+pub fn select<'a, 'b>(evaluations: &'b [Evaluation<'a>]) -> Option<&'b Evaluation<'a>> {
+    let rank = |e: &Evaluation| (e.priority, e.results.len());
+    let mut winner: Option<&Evaluation> = None;
+
+    for evaluation in evaluations.iter().filter(|e| e.is_eligible()) {
+        match winner {
+            Some(w) if rank(w) > rank(evaluation) => {}
+            Some(w) if rank(w) == rank(evaluation) => warn!(
+                "Presets '{}' and '{}' are equally suitable. Using '{}'",
+                w.preset.name, evaluation.preset.name, w.preset.name
+            ),
+            _ => winner = Some(evaluation),
+        }
+    }
+
+    winner
+}
+
+fn is_satisfied(condition: &Condition, preset: &Preset, current: &DisplayConfigState) -> bool {
+    let monitors = &current.monitors;
+
+    match condition {
+        Condition::Builtin { present } => monitors.iter().any(|m| m.is_builtin()) == *present,
+        Condition::Vendor { vendor } => monitors.iter().any(|m| &m.monitor_info.vendor == vendor),
+        Condition::Product { product } => {
+            monitors.iter().any(|m| &m.monitor_info.product == product)
+        }
+        Condition::MonitorCount { min, max } => {
+            min.is_none_or(|min| monitors.len() >= min)
+                && max.is_none_or(|max| monitors.len() <= max)
+        }
+        Condition::SameMonitors => {
+            saved_monitor_set(&preset.display_config) == monitor_set(current)
+        }
+    }
+}
+
+/// Finds preset which should be applied for currently connected monitors.
+pub fn find_matching_preset<'a>(
+    configuration: &'a Configuration,
+    current: &DisplayConfigState,
+) -> Option<&'a Preset> {
+    select(&evaluate(configuration, current)).map(|e| e.preset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::model::PropValue;
+
+    fn state(monitors: &[(&str, &str)]) -> DisplayConfigState {
+        fixtures::state(
+            monitors
+                .iter()
+                .map(|(connector, serial)| fixtures::monitor(connector, serial))
+                .collect(),
+        )
+    }
+
+    fn preset(name: &str, monitors: &[(&str, &str)]) -> Preset {
+        Preset {
+            name: name.to_string(),
+            display_config: PresetConfig::from_state(&state(monitors)),
+            rules: None,
+        }
+    }
+
+    #[test]
+    fn finds_preset_by_connected_monitors() {
+        let configuration = Configuration {
+            presets: vec![
+                preset("Laptop", &[("eDP-1", "")]),
+                preset("Dock", &[("eDP-1", ""), ("DP-3", "1"), ("DP-4", "2")]),
+            ],
+        };
+
+        let docked = state(&[("DP-6", "2"), ("eDP-1", ""), ("DP-5", "1")]);
+        let undocked = state(&[("eDP-1", "")]);
+        let unknown = state(&[("eDP-1", ""), ("HDMI-1", "3")]);
+
+        assert_eq!(
+            "Dock",
+            find_matching_preset(&configuration, &docked).unwrap().name
+        );
+        assert_eq!(
+            "Laptop",
+            find_matching_preset(&configuration, &undocked)
+                .unwrap()
+                .name
+        );
+        assert!(find_matching_preset(&configuration, &unknown).is_none());
+    }
+
+    fn preset_with_rules(name: &str, priority: i32, conditions: Vec<Condition>) -> Preset {
+        let mut preset = preset(name, &[]);
+        preset.rules = Some(SelectionRules {
+            priority,
+            conditions,
+        });
+        preset
+    }
+
+    #[test]
+    fn evaluates_conditions() {
+        let mut current = state(&[("eDP-1", ""), ("DP-1", "1"), ("DP-2", "2")]);
+        current.monitors[0]
+            .properties
+            .insert("is-builtin".to_string(), PropValue::Bool(true));
+        let preset = preset("P", &[]);
+
+        let satisfied = |c: Condition| is_satisfied(&c, &preset, &current);
+
+        assert!(satisfied(Condition::Builtin { present: true }));
+        assert!(!satisfied(Condition::Builtin { present: false }));
+        assert!(satisfied(Condition::Vendor {
+            vendor: "GSM".to_string()
+        }));
+        assert!(!satisfied(Condition::Vendor {
+            vendor: "DEL".to_string()
+        }));
+        assert!(satisfied(Condition::Product {
+            product: "LG Ultra HD".to_string()
+        }));
+        assert!(satisfied(Condition::MonitorCount {
+            min: Some(3),
+            max: None
+        }));
+        assert!(!satisfied(Condition::MonitorCount {
+            min: None,
+            max: Some(2)
+        }));
+        assert!(!satisfied(Condition::SameMonitors));
+    }
+
+    #[test]
+    fn selects_eligible_preset_with_highest_priority() {
+        let configuration = Configuration {
+            presets: vec![
+                preset_with_rules(
+                    "Any",
+                    0,
+                    vec![Condition::MonitorCount {
+                        min: Some(1),
+                        max: None,
+                    }],
+                ),
+                preset_with_rules(
+                    "LG",
+                    10,
+                    vec![Condition::Vendor {
+                        vendor: "GSM".to_string(),
+                    }],
+                ),
+                preset_with_rules(
+                    "Triple",
+                    20,
+                    vec![Condition::MonitorCount {
+                        min: Some(3),
+                        max: None,
+                    }],
+                ),
+            ],
+        };
+
+        let current = state(&[("eDP-1", ""), ("DP-1", "1")]);
+        let evaluations = evaluate(&configuration, &current);
+
+        assert_eq!("LG", select(&evaluations).unwrap().preset.name);
+        assert!(!evaluations[2].is_eligible());
+    }
+
+    #[test]
+    fn prefers_more_specific_preset_on_equal_priority() {
+        let configuration = Configuration {
+            presets: vec![
+                preset_with_rules(
+                    "Generic",
+                    0,
+                    vec![Condition::MonitorCount {
+                        min: Some(2),
+                        max: None,
+                    }],
+                ),
+                preset_with_rules(
+                    "Specific",
+                    0,
+                    vec![
+                        Condition::MonitorCount {
+                            min: Some(2),
+                            max: None,
+                        },
+                        Condition::Builtin { present: false },
+                    ],
+                ),
+            ],
+        };
+
+        let current = state(&[("DP-1", "1"), ("DP-2", "2")]);
+
+        assert_eq!(
+            "Specific",
+            find_matching_preset(&configuration, &current).unwrap().name
+        );
+    }
+}
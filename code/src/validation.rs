@@ -0,0 +1,236 @@
+use super::backend::DisplayBackend;
+use super::error::Error;
+use super::model::{DisplayConfigState, MonitorsConfig, PresetConfig};
+use super::mutter::{ApplyMethod, Rejection};
+use super::resolve::{self, ResolveError, ResolveOptions};
+
+#[derive(Debug, PartialEq)]
+pub struct MonitorIssue {
+    pub connector: String,
+    pub reason: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Accepted,
+    Rejected {
+        cause: Cause,
+        issues: Vec<MonitorIssue>,
+    },
+}
+
+/// Why a preset would not be applied.
+#[derive(Debug, PartialEq)]
+pub enum Cause {
+    /// Preset does not fit connected monitors.
+    Unresolved(ResolveError),
+    /// Mutter refused the configuration.
+    Rejected(Rejection),
+}
+
+impl std::fmt::Display for Cause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cause::Unresolved(err) => write!(f, "{}", err),
+            Cause::Rejected(rejection) => write!(f, "{}", rejection),
+        }
+    }
+}
+
+impl From<Cause> for Error {
+    fn from(cause: Cause) -> Self {
+        match cause {
+            Cause::Unresolved(err) => Error::Unresolved(err),
+            Cause::Rejected(rejection) => Error::Rejected(rejection),
+        }
+    }
+}
+
+/// Asks Mutter to verify display configuration without applying it.
+pub fn verify(
+    backend: &dyn DisplayBackend,
+    preset: &PresetConfig,
+    options: &ResolveOptions,
+) -> Result<Verdict, Error> {
+    let current_state = backend.get_current_state()?;
+
+    let config = match resolve::resolve_preset(preset, &current_state, options) {
+        Ok(config) => config,
+        Err(e) => return Ok(unresolved(e)),
+    };
+
+    match backend.apply_monitors_config(current_state.serial, ApplyMethod::Verify, &config) {
+        Ok(()) => Ok(Verdict::Accepted),
+        Err(Error::Rejected(rejection)) => {
+            let issues = explain_rejection(&rejection, &config, &current_state);
+            Ok(Verdict::Rejected {
+                cause: Cause::Rejected(rejection),
+                issues,
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn unresolved(err: ResolveError) -> Verdict {
+    let issues = match &err {
+        ResolveError::MissingMonitors(monitors) => monitors
+            .iter()
+            .map(|m| MonitorIssue {
+                connector: m.connector.clone(),
+                reason: format!("display {} is not connected", m.describe()),
+            })
+            .collect(),
+        ResolveError::MissingMode(monitor) => vec![MonitorIssue {
+            connector: monitor.connector.clone(),
+            reason: "preset does not contain mode for monitor".to_string(),
+        }],
+        ResolveError::ModeUnavailable { monitor, mode_id } => vec![MonitorIssue {
+            connector: monitor.connector.clone(),
+            reason: format!("mode '{}' is not supported by monitor", mode_id),
+        }],
+    };
+
+    Verdict::Rejected {
+        cause: Cause::Unresolved(err),
+        issues,
+    }
+}
+
+/// Compares resolved configuration with currently connected monitors to find out
+/// which monitors are the most likely reason of the rejection.
+pub fn explain_rejection(
+    rejection: &Rejection,
+    config: &MonitorsConfig,
+    current: &DisplayConfigState,
+) -> Vec<MonitorIssue> {
+    let mut issues = Vec::new();
+
+    for lm in &config.logical_monitors {
+        for monitor in &lm.monitors {
+            let issue = |reason: String| MonitorIssue {
+                connector: monitor.connector.clone(),
+                reason,
+            };
+
+            let mode = current
+                .monitors
+                .iter()
+                .find(|m| m.monitor_info.connector == monitor.connector)
+                .and_then(|m| m.modes.iter().find(|md| md.id == monitor.mode_id));
+
+            match (rejection, mode) {
+                (Rejection::InvalidArgs(_), None) => issues.push(issue(format!(
+                    "mode '{}' is not supported by monitor",
+                    monitor.mode_id
+                ))),
+                (Rejection::InvalidArgs(_), Some(mode))
+                    if !mode.supported_scales.contains(&lm.scale) =>
+                {
+                    issues.push(issue(format!(
+                        "scale {} is not supported by mode '{}'",
+                        lm.scale, monitor.mode_id
+                    )))
+                }
+                (Rejection::LimitsExceeded(_), Some(mode)) => issues.push(issue(format!(
+                    "mode {}x{} at position ({}, {}) may exceed screen size limits",
+                    mode.width, mode.height, lm.x, lm.y
+                ))),
+                _ => {}
+            }
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{monitor, state};
+    use crate::model::{LogicalMonitorConfig, MonitorConfig, Props};
+
+    fn current_state() -> DisplayConfigState {
+        state(vec![monitor("DP-1", "1")])
+    }
+
+    fn config(mode_id: &str, scale: f64) -> MonitorsConfig {
+        MonitorsConfig {
+            logical_monitors: vec![LogicalMonitorConfig {
+                x: 0,
+                y: 0,
+                scale,
+                transform: 0,
+                primary: true,
+                monitors: vec![MonitorConfig {
+                    connector: "DP-1".to_string(),
+                    mode_id: mode_id.to_string(),
+                    properties: Props::new(),
+                }],
+            }],
+            properties: Props::new(),
+        }
+    }
+
+    #[test]
+    fn explains_unsupported_mode() {
+        assert_eq!(
+            vec![MonitorIssue {
+                connector: "DP-1".to_string(),
+                reason: "mode '3840x2160@60' is not supported by monitor".to_string()
+            }],
+            explain_rejection(
+                &Rejection::InvalidArgs("".to_string()),
+                &config("3840x2160@60", 1.0),
+                &current_state()
+            )
+        );
+    }
+
+    #[test]
+    fn explains_unsupported_scale() {
+        assert_eq!(
+            vec![MonitorIssue {
+                connector: "DP-1".to_string(),
+                reason: "scale 1.5 is not supported by mode '1920x1080@60'".to_string()
+            }],
+            explain_rejection(
+                &Rejection::InvalidArgs("".to_string()),
+                &config("1920x1080@60", 1.5),
+                &current_state()
+            )
+        );
+    }
+
+    #[test]
+    fn explains_limits_exceeded() {
+        assert_eq!(
+            vec![MonitorIssue {
+                connector: "DP-1".to_string(),
+                reason: "mode 1920x1080 at position (0, 0) may exceed screen size limits"
+                    .to_string()
+            }],
+            explain_rejection(
+                &Rejection::LimitsExceeded("".to_string()),
+                &config("1920x1080@60", 1.0),
+                &current_state()
+            )
+        );
+    }
+
+    #[test]
+    fn explains_missing_display() {
+        let monitor = current_state().monitors.remove(0).monitor_info;
+
+        assert_eq!(
+            vec![MonitorIssue {
+                connector: "DP-1".to_string(),
+                reason: format!("display {} is not connected", monitor.describe())
+            }],
+            match unresolved(ResolveError::MissingMonitors(vec![monitor])) {
+                Verdict::Rejected { issues, .. } => issues,
+                Verdict::Accepted => vec![],
+            }
+        );
+    }
+}
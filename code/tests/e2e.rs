@@ -0,0 +1,313 @@
+//! Runs the binary against mock-mutter on a private D-Bus session bus.
+//! Tests are skipped when dbus-daemon is not installed.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, ChildStdout, Command, Output, Stdio};
+use uuid::Uuid;
+
+const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/laptop-and-monitor.json"
+);
+
+/// Private session bus with mock-mutter serving the fixture.
+struct Session {
+    dbus_daemon: Child,
+    mock_mutter: Child,
+    /// Kept open so that the mock can keep writing to stdout.
+    _mock_mutter_output: BufReader<ChildStdout>,
+    address: String,
+    config_path: String,
+}
+
+impl Session {
+    fn start(fixture: &str) -> Option<Session> {
+        let mut dbus_daemon = match Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Skipping, dbus-daemon can't be started: {}", e);
+                return None;
+            }
+        };
+        let address = read_line(dbus_daemon.stdout.take().unwrap()).0;
+
+        let mut mock_mutter = Command::new(env!("CARGO_BIN_EXE_mock-mutter"))
+            .arg(fixture)
+            .env("DBUS_SESSION_BUS_ADDRESS", &address)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let (ready, output) = read_line(mock_mutter.stdout.take().unwrap());
+        assert!(ready.starts_with("Serving"), "mock-mutter: {}", ready);
+
+        Some(Session {
+            dbus_daemon,
+            mock_mutter,
+            _mock_mutter_output: output,
+            address,
+            config_path: format!("/tmp/{}.json", Uuid::new_v4()),
+        })
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_mutter-display-presets"))
+            .args(["-c", &self.config_path])
+            .args(args)
+            .env("DBUS_SESSION_BUS_ADDRESS", &self.address)
+            .output()
+            .unwrap()
+    }
+
+    fn run_ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "{:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn edit_preset(&self, name: &str, edit: impl FnOnce(&mut Value)) {
+        let mut config: Value =
+            serde_json::from_str(&std::fs::read_to_string(&self.config_path).unwrap()).unwrap();
+        let preset = config["presets"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|p| p["name"] == name)
+            .unwrap();
+        edit(&mut preset["display_config"]);
+        std::fs::write(&self.config_path, config.to_string()).unwrap();
+    }
+
+    fn current_mode(&self, connector: &str) -> String {
+        self.run_ok(&["save", "current", "--force"]);
+
+        let config: Value =
+            serde_json::from_str(&std::fs::read_to_string(&self.config_path).unwrap()).unwrap();
+        let preset = config["presets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|p| p["name"] == "current")
+            .unwrap();
+        let monitor = preset["display_config"]["logical_monitors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|lm| lm["monitors"].as_array().unwrap())
+            .find(|m| m["monitor_info"]["connector"] == connector)
+            .unwrap();
+        monitor["mode"]["id"].as_str().unwrap().to_string()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.mock_mutter.kill();
+        let _ = self.mock_mutter.wait();
+        let _ = self.dbus_daemon.kill();
+        let _ = self.dbus_daemon.wait();
+        let _ = std::fs::remove_file(&self.config_path);
+    }
+}
+
+fn read_line(stdout: ChildStdout) -> (String, BufReader<ChildStdout>) {
+    let mut reader = BufReader::new(stdout);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    (line.trim().to_string(), reader)
+}
+
+/// Copy of the fixture modified by `edit`.
+fn custom_fixture(edit: impl FnOnce(&mut Value)) -> String {
+    let mut fixture: Value =
+        serde_json::from_str(&std::fs::read_to_string(FIXTURE).unwrap()).unwrap();
+    edit(&mut fixture);
+
+    let path = format!("/tmp/{}.json", Uuid::new_v4());
+    std::fs::write(&path, fixture.to_string()).unwrap();
+    path
+}
+
+/// Makes the external monitor of the fixture use 4K mode with scale 2 in the preset.
+fn switch_to_4k(display_config: &mut Value) {
+    let logical_monitor = &mut display_config["logical_monitors"][1];
+    logical_monitor["monitors"][0]["mode"] = json!({
+        "id": "3840x2160@60.000",
+        "width": 3840,
+        "height": 2160,
+        "refresh_rate": 60.0
+    });
+    logical_monitor["scale"] = json!(2.0);
+}
+
+#[test]
+fn saves_and_applies_preset() {
+    let Some(session) = Session::start(FIXTURE) else {
+        return;
+    };
+
+    session.run_ok(&["save", "desk"]);
+    assert!(session.run_ok(&["list"]).contains("desk"));
+
+    session.run_ok(&["apply", "desk"]);
+    assert_eq!("2560x1440@59.951", session.current_mode("DP-1"));
+}
+
+#[test]
+fn applies_mode_from_preset() {
+    let Some(session) = Session::start(FIXTURE) else {
+        return;
+    };
+
+    session.run_ok(&["save", "desk"]);
+    session.edit_preset("desk", switch_to_4k);
+
+    session.run_ok(&["apply", "desk"]);
+    assert_eq!("3840x2160@60.000", session.current_mode("DP-1"));
+}
+
+#[test]
+fn warns_about_closest_mode_without_verbose() {
+    let Some(session) = Session::start(FIXTURE) else {
+        return;
+    };
+
+    session.run_ok(&["save", "desk"]);
+    session.edit_preset("desk", |display_config| {
+        switch_to_4k(display_config);
+        display_config["logical_monitors"][1]["monitors"][0]["mode"]["id"] =
+            json!("3840x2160@61.000");
+    });
+
+    let output = session.run(&["apply", "desk"]);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Using closest mode '3840x2160@60.000'")
+    );
+}
+
+#[test]
+fn dry_run_does_not_change_configuration() {
+    let Some(session) = Session::start(FIXTURE) else {
+        return;
+    };
+
+    session.run_ok(&["save", "desk"]);
+    session.edit_preset("desk", switch_to_4k);
+
+    assert!(session
+        .run_ok(&["apply", "desk", "--dry-run"])
+        .contains("OK"));
+    assert_eq!("2560x1440@59.951", session.current_mode("DP-1"));
+}
+
+#[test]
+fn validate_reports_rejected_scale() {
+    let Some(session) = Session::start(FIXTURE) else {
+        return;
+    };
+
+    session.run_ok(&["save", "desk"]);
+    session.edit_preset("desk", |display_config| {
+        display_config["logical_monitors"][1]["scale"] = json!(1.5);
+    });
+
+    let output = session.run(&["validate", "desk"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("Scale 1.5 not valid for resolution 2560x1440"));
+}
+
+#[test]
+fn fails_to_apply_unknown_preset() {
+    let Some(session) = Session::start(FIXTURE) else {
+        return;
+    };
+
+    assert_eq!(Some(5), session.run(&["apply", "desk"]).status.code());
+}
+
+#[test]
+fn exits_with_code_of_error() {
+    let Some(session) = Session::start(FIXTURE) else {
+        return;
+    };
+
+    session.run_ok(&["save", "desk"]);
+    assert_eq!(Some(6), session.run(&["save", "desk"]).status.code());
+
+    session.edit_preset("desk", |display_config| {
+        display_config["logical_monitors"][1]["scale"] = json!(1.5);
+    });
+    assert_eq!(Some(10), session.run(&["apply", "desk"]).status.code());
+
+    std::fs::write(&session.config_path, "{\"presets\": [}").unwrap();
+    let output = session.run(&["list"]);
+    assert_eq!(Some(4), output.status.code());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(&format!("{}:1:14", session.config_path))
+    );
+}
+
+#[test]
+fn refuses_to_apply_when_not_allowed() {
+    let fixture = custom_fixture(|fixture| fixture["apply_monitors_config_allowed"] = json!(false));
+    let Some(session) = Session::start(&fixture) else {
+        return;
+    };
+
+    session.run_ok(&["save", "desk"]);
+    let output = session.run(&["apply", "desk"]);
+
+    assert_eq!(Some(13), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not allow"));
+    std::fs::remove_file(fixture).unwrap();
+}
+
+#[test]
+fn doctor_reports_mock_as_mutter_service() {
+    let Some(session) = Session::start(FIXTURE) else {
+        return;
+    };
+
+    let report: Value = serde_json::from_str(&session.run_ok(&["doctor", "--json"])).unwrap();
+    let service = report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == "Mutter service")
+        .unwrap();
+
+    assert_eq!("pass", service["status"]);
+}
+
+#[test]
+fn doctor_json_exits_with_error_when_checks_fail() {
+    let Some(session) = Session::start(FIXTURE) else {
+        return;
+    };
+    std::fs::write(&session.config_path, "{ not json").unwrap();
+
+    let output = session.run(&["doctor", "--json"]);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let config = report["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["name"] == "Configuration file")
+        .unwrap();
+
+    assert_eq!("fail", config["status"]);
+    assert_eq!(Some(1), output.status.code());
+}